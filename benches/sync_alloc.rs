@@ -0,0 +1,66 @@
+//! Compare allocating small nodes from many threads through a shared
+//! `SyncArena`, and through per-thread `LocalHandle`s.
+//!
+//! Run with `cargo bench --bench sync_alloc`.
+
+use data_arena::source::AllocSource;
+use data_arena::SyncArena;
+
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ALLOCS_PER_THREAD: u64 = 1_000_000;
+
+/// Keep an allocation from being optimized away, as `std::hint::black_box`
+/// is newer than the crate's minimum supported Rust version.
+fn black_box<T>(value: &mut T) {
+    unsafe { ptr::read_volatile(&(value as *mut T)) };
+}
+
+#[derive(Copy, Clone)]
+#[allow(dead_code)]
+struct Node {
+    value: u64,
+    next: Option<&'static Node>,
+}
+
+fn run(threads: u64, local: bool) -> Duration {
+    let arena = SyncArena::with_source(AllocSource::new(1 << 20));
+    let start = Instant::now();
+    thread::scope(|s| {
+        for t in 0..threads {
+            let arena = &arena;
+            s.spawn(move || {
+                let node = Node {
+                    value: t,
+                    next: None,
+                };
+                if local {
+                    let handle = arena.local_handle();
+                    for _ in 0..ALLOCS_PER_THREAD {
+                        black_box(handle.alloc(node));
+                    }
+                } else {
+                    for _ in 0..ALLOCS_PER_THREAD {
+                        black_box(arena.alloc(node));
+                    }
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let max_threads = thread::available_parallelism().map_or(4, |n| n.get() as u64);
+    println!("{:>8} {:>16} {:>16}", "threads", "shared ns/alloc", "local ns/alloc");
+    let mut threads = 1;
+    while threads <= max_threads {
+        let total = (threads * ALLOCS_PER_THREAD) as f64;
+        let shared = run(threads, false).as_nanos() as f64 / total;
+        let local = run(threads, true).as_nanos() as f64 / total;
+        println!("{:>8} {:>16.2} {:>16.2}", threads, shared, local);
+        threads *= 2;
+    }
+}
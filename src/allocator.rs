@@ -0,0 +1,71 @@
+//! Implementations of the `Allocator` trait for arena references, allowing
+//! collections such as `Vec<T, &Arena>` to allocate from an arena.
+//!
+//! Memory is only returned to the arena when the most recent allocation is
+//! deallocated, or resized in place.
+
+use crate::lock::RawLock;
+use crate::source::SlabSource;
+use crate::{Arena, SyncArena};
+
+use core::alloc::Layout;
+use core::ptr::{self, NonNull};
+
+macro_rules! impl_allocator {
+    ($Allocator:path, $AllocError:path, $Arena:ident $(<$L:ident: $LockBound:path>)?) => {
+        unsafe impl<'s, 'a, S: SlabSource $(, $L: $LockBound)?> $Allocator
+            for &'s $Arena<'a, S $(, $L)?>
+        {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, $AllocError> {
+                let ptr = unsafe { self.try_alloc_raw(layout) }.map_err(|_| $AllocError)?;
+                Ok(slice_ptr(ptr, layout.size()))
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.resize_in_place(ptr, layout.size(), 0);
+            }
+
+            unsafe fn grow(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, $AllocError> {
+                let new = self
+                    .try_realloc_raw(ptr, old_layout, new_layout)
+                    .map_err(|_| $AllocError)?;
+                Ok(slice_ptr(new, new_layout.size()))
+            }
+
+            unsafe fn shrink(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, $AllocError> {
+                let new = self
+                    .try_realloc_raw(ptr, old_layout, new_layout)
+                    .map_err(|_| $AllocError)?;
+                Ok(slice_ptr(new, new_layout.size()))
+            }
+        }
+    };
+}
+
+fn slice_ptr(ptr: NonNull<u8>, len: usize) -> NonNull<[u8]> {
+    unsafe { NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(ptr.as_ptr(), len)) }
+}
+
+#[cfg(feature = "allocator_api")]
+impl_allocator!(core::alloc::Allocator, core::alloc::AllocError, Arena);
+#[cfg(feature = "allocator_api")]
+impl_allocator!(core::alloc::Allocator, core::alloc::AllocError, SyncArena<L: RawLock>);
+
+#[cfg(feature = "allocator-api2")]
+impl_allocator!(allocator_api2::alloc::Allocator, allocator_api2::alloc::AllocError, Arena);
+#[cfg(feature = "allocator-api2")]
+impl_allocator!(
+    allocator_api2::alloc::Allocator,
+    allocator_api2::alloc::AllocError,
+    SyncArena<L: RawLock>
+);
@@ -1,5 +1,10 @@
-use crate::slab::{alloc_in_slab_nonatomic, alloc_slow, arena_drop, SlabHeader};
+use crate::drops::{run_drops, DropRecord};
+use crate::frozen::{FrozenArena, FrozenRoot};
+use crate::slab::{
+    alloc_in_slab_nonatomic, alloc_slow, arena_drop, resize_in_slab_nonatomic, SlabHeader, Slabs,
+};
 use crate::source::SlabSource;
+use crate::ArenaError;
 
 use core::alloc::Layout;
 use core::cell::{Cell, RefCell};
@@ -11,17 +16,28 @@ pub struct Arena<'a, S: SlabSource> {
     slab: Cell<Option<NonNull<SlabHeader>>>,
     // NOTE: This could _probably_ be an UnsafeCell, with the requirement that
     // SlabSource impls cannot be re-entrant.
-    source: RefCell<S>,
-    marker: PhantomData<&'a ()>,
+    slabs: RefCell<Slabs<S>>,
+    drops: Cell<Option<NonNull<DropRecord>>>,
+    // Invariant, as destructors registered by `alloc_drop` may borrow for 'a.
+    marker: PhantomData<fn(&'a ()) -> &'a ()>,
 }
 
 arena_common!(Arena);
 
 impl<'a, S: SlabSource> Arena<'a, S> {
-    pub unsafe fn try_alloc_raw(&self, layout: Layout) -> Option<NonNull<u8>> {
+    pub const fn with_source(source: S) -> Self {
+        Arena {
+            slab: Cell::new(None),
+            slabs: RefCell::new(Slabs::new(source)),
+            drops: Cell::new(None),
+            marker: PhantomData,
+        }
+    }
+
+    pub unsafe fn try_alloc_raw(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError> {
         let slab = self.slab.get();
         if let Some(ptr) = alloc_in_slab_nonatomic(slab, layout) {
-            return Some(ptr);
+            return Ok(ptr);
         }
 
         self.try_alloc_raw_slow(layout, slab)
@@ -32,18 +48,117 @@ impl<'a, S: SlabSource> Arena<'a, S> {
         &self,
         layout: Layout,
         old_slab: Option<NonNull<SlabHeader>>,
-    ) -> Option<NonNull<u8>> {
-        let mut source = self.source.borrow_mut();
-        let (slab, ptr) = alloc_slow(&mut *source, layout, old_slab)?;
-        self.slab.set(Some(slab));
-        Some(ptr)
+    ) -> Result<NonNull<u8>, ArenaError> {
+        let mut slabs = self.slabs.borrow_mut();
+        let (slab, ptr) = alloc_slow(&mut *slabs, layout, old_slab)?;
+        self.slab.set(slab);
+        Ok(ptr)
+    }
+
+    pub(crate) unsafe fn resize_in_place(&self, ptr: NonNull<u8>, old_size: usize, new_size: usize) -> bool {
+        resize_in_slab_nonatomic(self.slab.get(), ptr, old_size, new_size)
+    }
+
+    /// Stop allocating from this arena, so it can be shared between threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any destructors were registered with `alloc_drop`, as they
+    /// could be run on another thread.
+    pub fn freeze(self) -> FrozenArena<'a, S> {
+        FrozenArena::new(self, |_| &())
+    }
+
+    /// Like [`freeze`](Self::freeze), but first calls `build` to get a
+    /// reference to the root of the data in the arena, which can be reached
+    /// with [`FrozenArena::with_root`].
+    ///
+    /// `build` is lent the arena for the lifetime of its borrow, so values
+    /// allocated in it can hold references to each other, but not to data
+    /// which isn't `'static`. The root's type is named by `R`; see
+    /// [`FrozenRoot`](crate::FrozenRoot).
+    ///
+    /// # Panics
+    ///
+    /// Panics if any destructors were registered with `alloc_drop`, before or
+    /// during `build`. Destructors registered by `build` are leaked.
+    pub fn freeze_with<R: for<'r> FrozenRoot<'r>>(
+        self,
+        build: impl for<'r> FnOnce(&'r Arena<'r, S>) -> &'r <R as FrozenRoot<'r>>::Root,
+    ) -> FrozenArena<'a, S, R> {
+        FrozenArena::new(self, build)
+    }
+
+    /// Convert this into a [`SyncArena`](crate::SyncArena), keeping every
+    /// existing allocation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any destructors were registered with `alloc_drop`, as they
+    /// may not be `Send`.
+    pub fn into_sync(self) -> crate::SyncArena<'a, S> {
+        assert!(
+            !self.has_drops(),
+            "cannot convert an arena with registered destructors"
+        );
+        let this = core::mem::ManuallyDrop::new(self);
+        let slabs = unsafe { ptr::read(&this.slabs) }.into_inner();
+        unsafe { crate::SyncArena::from_parts(this.slab.get(), slabs, None) }
+    }
+
+    /// Rebuild an arena from the parts of another arena.
+    pub(crate) unsafe fn from_parts(
+        slab: Option<NonNull<SlabHeader>>,
+        slabs: Slabs<S>,
+        drops: Option<NonNull<DropRecord>>,
+    ) -> Self {
+        Arena {
+            slab: Cell::new(slab),
+            slabs: RefCell::new(slabs),
+            drops: Cell::new(drops),
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn has_drops(&self) -> bool {
+        self.drops.get().is_some()
+    }
+
+    fn with_slabs<R>(&self, f: impl FnOnce(&mut Slabs<S>, Option<NonNull<SlabHeader>>) -> R) -> R {
+        f(&mut *self.slabs.borrow_mut(), self.slab.get())
+    }
+
+    fn replace_slab(&mut self, slab: Option<NonNull<SlabHeader>>) -> Option<NonNull<SlabHeader>> {
+        self.slab.replace(slab)
+    }
+
+    fn slabs_mut(&mut self) -> &mut Slabs<S> {
+        self.slabs.get_mut()
+    }
+
+    unsafe fn push_drop(&self, record: NonNull<DropRecord>) {
+        DropRecord::set_prev(record, self.drops.get());
+        self.drops.set(Some(record));
+    }
+
+    fn current_drops(&self) -> Option<NonNull<DropRecord>> {
+        self.drops.get()
+    }
+
+    fn take_drops(&mut self) -> Option<NonNull<DropRecord>> {
+        self.drops.take()
+    }
+
+    fn replace_drops(&mut self, drops: Option<NonNull<DropRecord>>) {
+        self.drops.set(drops);
     }
 }
 
 impl<'a, S: SlabSource> Drop for Arena<'a, S> {
     fn drop(&mut self) {
         unsafe {
-            arena_drop(self.source.get_mut(), self.slab.get());
+            run_drops(self.drops.take(), None);
+            arena_drop(self.slabs.get_mut(), self.slab.get());
         }
     }
 }
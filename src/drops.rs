@@ -0,0 +1,56 @@
+use core::mem;
+use core::ptr::{self, NonNull};
+
+/// A destructor which must be run before an arena is freed. Records are
+/// allocated in the arena's own slabs, and linked from newest to oldest.
+pub(crate) struct DropRecord {
+    prev: Option<NonNull<DropRecord>>,
+    ptr: NonNull<u8>,
+    len: usize,
+    drop_fn: unsafe fn(NonNull<u8>, usize),
+}
+
+unsafe fn drop_slice<T>(ptr: NonNull<u8>, len: usize) {
+    let slice = ptr::slice_from_raw_parts_mut(ptr.cast::<T>().as_ptr(), len);
+    ptr::drop_in_place(slice);
+}
+
+impl DropRecord {
+    /// Returns `None` if dropping `[T]` is a no-op, meaning no record needs to
+    /// be registered.
+    pub(crate) fn new<T>(ptr: NonNull<T>, len: usize) -> Option<Self> {
+        if !mem::needs_drop::<T>() || len == 0 {
+            return None;
+        }
+        Some(DropRecord {
+            prev: None,
+            ptr: ptr.cast::<u8>(),
+            len,
+            drop_fn: drop_slice::<T>,
+        })
+    }
+
+    pub(crate) unsafe fn set_prev(
+        mut record: NonNull<DropRecord>,
+        prev: Option<NonNull<DropRecord>>,
+    ) {
+        record.as_mut().prev = prev;
+    }
+}
+
+/// Run each destructor in the list starting at `ptr`, stopping when `stop` is
+/// reached. As the list is newest-first, this runs them in reverse allocation
+/// order.
+pub(crate) unsafe fn run_drops(
+    mut ptr: Option<NonNull<DropRecord>>,
+    stop: Option<NonNull<DropRecord>>,
+) {
+    while ptr != stop {
+        let record = match ptr {
+            Some(record) => record.as_ptr().read(),
+            None => panic!("checkpoint was not taken from this arena"),
+        };
+        ptr = record.prev;
+        (record.drop_fn)(record.ptr, record.len);
+    }
+}
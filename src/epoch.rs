@@ -0,0 +1,231 @@
+use crate::lock::{DefaultLock, RawLock};
+use crate::slab::RetiredSlabs;
+use crate::source::{InfallibleSource, SlabSource};
+use crate::sync::{AtomicUsize, Ordering};
+use crate::{ArenaError, RawArena, SyncArena};
+
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ptr::NonNull;
+
+/// A [`SyncArena`] whose memory is recycled in epochs, so that it can be
+/// reused without `&mut` access to reset it.
+///
+/// Threads allocate through an [`EpochGuard`] returned by [`pin`](Self::pin),
+/// and the allocations live as long as the guard. Each epoch allocates from
+/// its own slabs. [`advance_epoch`](Self::advance_epoch) starts a new epoch,
+/// and recycles the slabs of the epoch before the current one, once every
+/// guard pinned in it has been dropped.
+///
+/// Destructors can't be registered, as memory is recycled without waiting for
+/// the arena to be dropped.
+pub struct EpochArena<'a, S: SlabSource, L: RawLock = DefaultLock> {
+    arena: SyncArena<'a, S, L>,
+    epoch: AtomicUsize,
+    // The number of live guards pinned in each epoch, indexed by its parity.
+    pins: [AtomicUsize; 2],
+    // The slabs of the previous epoch, which are only accessed while the
+    // arena's lock is held.
+    retired: UnsafeCell<Option<RetiredSlabs>>,
+}
+
+// The retired slabs are only accessed with the arena's lock held, so are
+// shared like the rest of the arena's slabs.
+unsafe impl<'a, S: SlabSource, L: RawLock> Send for EpochArena<'a, S, L> where
+    SyncArena<'a, S, L>: Send
+{
+}
+unsafe impl<'a, S: SlabSource, L: RawLock> Sync for EpochArena<'a, S, L> where
+    SyncArena<'a, S, L>: Sync
+{
+}
+
+#[cfg(any(feature = "alloc", feature = "std"))]
+impl<'a> EpochArena<'a, crate::source::AllocSource> {
+    /// Create a new arena with the default allocation strategy.
+    pub fn new() -> Self {
+        Self::with_source(Default::default())
+    }
+}
+
+impl<'a, S: SlabSource + Default> Default for EpochArena<'a, S> {
+    fn default() -> Self {
+        Self::with_source(Default::default())
+    }
+}
+
+impl<'a, S: SlabSource> EpochArena<'a, S> {
+    pub fn with_source(source: S) -> Self {
+        Self::from_arena(SyncArena::with_source(source))
+    }
+}
+
+impl<'a, S: SlabSource, L: RawLock> EpochArena<'a, S, L> {
+    /// Create an arena which serializes calls into `source` with `lock`.
+    pub fn with_source_and_lock(source: S, lock: L) -> Self {
+        Self::from_arena(SyncArena::with_source_and_lock(source, lock))
+    }
+
+    fn from_arena(arena: SyncArena<'a, S, L>) -> Self {
+        EpochArena {
+            arena,
+            epoch: AtomicUsize::new(0),
+            pins: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            retired: UnsafeCell::new(None),
+        }
+    }
+
+    /// The current epoch, counting up from zero.
+    pub fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Pin the current epoch, so that memory allocated through the returned
+    /// guard isn't recycled until it is dropped.
+    pub fn pin(&self) -> EpochGuard<'_, 'a, S, L> {
+        loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let pins = &self.pins[epoch % 2];
+            pins.fetch_add(1, Ordering::SeqCst);
+
+            // If the epoch advanced before the pin was counted, the counter
+            // may already have been checked, so try again in the new epoch.
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                return EpochGuard { arena: self, epoch };
+            }
+            pins.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Start a new epoch, with new slabs, and recycle the slabs used by the
+    /// epoch before the current one.
+    ///
+    /// Returns `false` without advancing if guards pinned in the previous
+    /// epoch are still alive, as they may still be using those slabs.
+    pub fn advance_epoch(&self) -> bool {
+        let mut slabs = self.arena.lock_slabs();
+        let epoch = self.epoch.load(Ordering::SeqCst);
+
+        // Pairs with the `Release` ordering used when guards are dropped, so
+        // their accesses happen before the slabs are reused.
+        if self.pins[epoch.wrapping_sub(1) % 2].load(Ordering::SeqCst) != 0 {
+            return false;
+        }
+
+        // The lock is held, so nothing else is using `retired`.
+        let retired = unsafe { &mut *self.retired.get() };
+        if let Some(prev) = retired.take() {
+            unsafe { slabs.recycle_retired(prev) }
+        }
+        *retired = Some(self.arena.retire_slabs(&mut slabs));
+        self.epoch.store(epoch.wrapping_add(1), Ordering::SeqCst);
+        true
+    }
+}
+
+impl<'a, S: SlabSource, L: RawLock> Drop for EpochArena<'a, S, L> {
+    fn drop(&mut self) {
+        // Hand the retired slabs back to the arena, so they're freed with it.
+        if let Some(retired) = self.retired.get_mut().take() {
+            unsafe { self.arena.lock_slabs().recycle_retired(retired) }
+        }
+    }
+}
+
+impl<'a, S: SlabSource, L: RawLock> fmt::Debug for EpochArena<'a, S, L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EpochArena")
+            .field("epoch", &self.epoch())
+            .finish()
+    }
+}
+
+/// A pin on an epoch of an [`EpochArena`], created by [`EpochArena::pin`].
+///
+/// Allocations made through the guard live as long as it does.
+pub struct EpochGuard<'e, 'a, S: SlabSource, L: RawLock = DefaultLock> {
+    arena: &'e EpochArena<'a, S, L>,
+    epoch: usize,
+}
+
+impl<'e, 'a, S: SlabSource, L: RawLock> EpochGuard<'e, 'a, S, L> {
+    /// The epoch which this guard pinned.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    pub unsafe fn try_alloc_raw(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError> {
+        self.arena.arena.try_alloc_raw(layout)
+    }
+
+    /// Resize a block previously allocated from this guard, as with
+    /// `SyncArena::try_realloc_raw`.
+    pub unsafe fn try_realloc_raw(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, ArenaError> {
+        self.arena
+            .arena
+            .try_realloc_raw(ptr, old_layout, new_layout)
+    }
+
+    pub fn try_alloc<T: Copy + 'a>(&self, t: T) -> Result<&mut T, ArenaError> {
+        self.arena.arena.try_alloc(t)
+    }
+
+    pub fn try_alloc_slice<T: Copy + 'a>(&self, t: &[T]) -> Result<&mut [T], ArenaError> {
+        self.arena.arena.try_alloc_slice(t)
+    }
+
+    pub fn try_alloc_str(&self, s: &str) -> Result<&mut str, ArenaError> {
+        self.arena.arena.try_alloc_str(s)
+    }
+}
+
+impl<'e, 'a, S: InfallibleSource, L: RawLock> EpochGuard<'e, 'a, S, L> {
+    pub fn alloc<T: Copy + 'a>(&self, t: T) -> &mut T {
+        self.arena.arena.alloc(t)
+    }
+
+    pub fn alloc_slice<T: Copy + 'a>(&self, t: &[T]) -> &mut [T] {
+        self.arena.arena.alloc_slice(t)
+    }
+
+    pub fn alloc_str(&self, s: &str) -> &mut str {
+        self.arena.arena.alloc_str(s)
+    }
+}
+
+unsafe impl<'e, 'a, S: SlabSource, L: RawLock> RawArena for EpochGuard<'e, 'a, S, L> {
+    type Source = S;
+
+    unsafe fn try_alloc_raw(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError> {
+        EpochGuard::try_alloc_raw(self, layout)
+    }
+
+    unsafe fn try_realloc_raw(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, ArenaError> {
+        EpochGuard::try_realloc_raw(self, ptr, old_layout, new_layout)
+    }
+}
+
+impl<'e, 'a, S: SlabSource, L: RawLock> Drop for EpochGuard<'e, 'a, S, L> {
+    fn drop(&mut self) {
+        self.arena.pins[self.epoch % 2].fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<'e, 'a, S: SlabSource, L: RawLock> fmt::Debug for EpochGuard<'e, 'a, S, L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EpochGuard")
+            .field("epoch", &self.epoch)
+            .finish()
+    }
+}
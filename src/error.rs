@@ -0,0 +1,38 @@
+use core::alloc::Layout;
+use core::fmt;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+/// The reason an allocation from an arena failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArenaError {
+    /// The size of the requested allocation overflowed.
+    LayoutOverflow,
+    /// The source has run out of memory to hand out, and couldn't provide a
+    /// slab with the `requested` layout.
+    SourceExhausted { requested: Layout },
+    /// The source's underlying allocator or the operating system failed.
+    SourceFailed,
+    /// Providing another slab would exceed the quota set on the source.
+    QuotaExceeded,
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArenaError::LayoutOverflow => f.write_str("allocation size overflowed"),
+            ArenaError::SourceExhausted { requested } => write!(
+                f,
+                "slab source exhausted while allocating {} bytes aligned to {}",
+                requested.size(),
+                requested.align()
+            ),
+            ArenaError::SourceFailed => f.write_str("slab source failed to allocate"),
+            ArenaError::QuotaExceeded => f.write_str("slab source quota exceeded"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ArenaError {}
@@ -0,0 +1,143 @@
+use crate::source::SlabSource;
+use crate::Arena;
+
+use core::fmt;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+/// Names the type of a [`FrozenArena`]'s root for each lifetime the arena can
+/// be borrowed for, so the root can hold references into the arena.
+///
+/// This is usually implemented by an empty marker type:
+///
+/// ```
+/// use data_arena::{Arena, FrozenRoot};
+///
+/// #[derive(Clone, Copy)]
+/// struct Node<'r> {
+///     value: u32,
+///     next: Option<&'r Node<'r>>,
+/// }
+///
+/// struct List;
+///
+/// impl<'r> FrozenRoot<'r> for List {
+///     type Root = Node<'r>;
+/// }
+///
+/// let arena = Arena::new();
+/// let frozen = arena.freeze_with::<List>(|arena| {
+///     let tail = arena.alloc(Node { value: 2, next: None });
+///     arena.alloc(Node { value: 1, next: Some(tail) })
+/// });
+/// frozen.with_root(|head| assert_eq!(head.next.unwrap().value, 2));
+/// ```
+pub trait FrozenRoot<'r> {
+    type Root: ?Sized + 'r;
+}
+
+impl<'r> FrozenRoot<'r> for () {
+    type Root = ();
+}
+
+/// An [`Arena`] which can no longer be allocated from, so can be shared
+/// between threads.
+///
+/// Created with [`Arena::freeze`] or [`Arena::freeze_with`]. The data in the
+/// arena is reached through its root, which is only lent as a shared reference
+/// to closures passed to [`with_root`](Self::with_root). The type of the root
+/// is given by `R`, which implements [`FrozenRoot`].
+pub struct FrozenArena<'a, S: SlabSource, R: for<'r> FrozenRoot<'r> = ()> {
+    arena: Arena<'a, S>,
+    // Really borrows from `arena`. The lifetime is replaced with a borrow of
+    // `self` when the root is lent out by `with_root`.
+    root: NonNull<<R as FrozenRoot<'static>>::Root>,
+}
+
+// The arena is never allocated from or borrowed mutably while frozen, and has
+// no destructors to run, so the only data which is shared is the root.
+unsafe impl<'a, S, R> Send for FrozenArena<'a, S, R>
+where
+    S: SlabSource + Send,
+    R: for<'r> FrozenRoot<'r>,
+    for<'r> <R as FrozenRoot<'r>>::Root: Sync,
+{
+}
+unsafe impl<'a, S, R> Sync for FrozenArena<'a, S, R>
+where
+    S: SlabSource + Send,
+    R: for<'r> FrozenRoot<'r>,
+    for<'r> <R as FrozenRoot<'r>>::Root: Sync,
+{
+}
+
+impl<'a, S: SlabSource, R: for<'r> FrozenRoot<'r>> FrozenArena<'a, S, R> {
+    pub(crate) fn new(
+        arena: Arena<'a, S>,
+        build: impl for<'r> FnOnce(&'r Arena<'r, S>) -> &'r <R as FrozenRoot<'r>>::Root,
+    ) -> Self {
+        assert!(
+            !arena.has_drops(),
+            "cannot freeze an arena with registered destructors"
+        );
+        // Lend the arena to `build` for only as long as it is borrowed, so the
+        // values allocated in it can refer to each other.
+        let lent: &Arena<'_, S> = unsafe { &*(&arena as *const Arena<'a, S>).cast() };
+        let root = NonNull::from(build(lent));
+        if arena.has_drops() {
+            // The destructors may refer to values which have already been
+            // dropped, so can't be run.
+            mem::forget(arena);
+            panic!("cannot freeze an arena with registered destructors");
+        }
+        // Only the lifetime differs, and it is restored in `with_root`.
+        let root = unsafe { ptr::read((&root as *const NonNull<_>).cast()) };
+        FrozenArena { arena, root }
+    }
+
+    /// Call `f` with a reference to the root.
+    ///
+    /// `f` must accept the root for any lifetime, so it can't store anything
+    /// in the root which doesn't live as long as the arena. A root which
+    /// could be borrowed for a shorter lifetime would allow this:
+    ///
+    /// ```compile_fail
+    /// use data_arena::{Arena, FrozenRoot};
+    /// use std::cell::Cell;
+    ///
+    /// struct Slot;
+    ///
+    /// impl<'r> FrozenRoot<'r> for Slot {
+    ///     type Root = Cell<Option<&'r String>>;
+    /// }
+    ///
+    /// let arena = Arena::new();
+    /// let frozen = arena.freeze_with::<Slot>(|arena| arena.alloc_no_drop(Cell::new(None)));
+    /// {
+    ///     let local = String::from("dropped before the root");
+    ///     frozen.with_root(|slot| slot.set(Some(&local)));
+    /// }
+    /// frozen.with_root(|slot| println!("{:?}", slot.get()));
+    /// ```
+    pub fn with_root<U>(&self, f: impl for<'r> FnOnce(&'r <R as FrozenRoot<'r>>::Root) -> U) -> U {
+        let root: NonNull<<R as FrozenRoot<'_>>::Root> =
+            unsafe { ptr::read((&self.root as *const NonNull<_>).cast()) };
+        f(unsafe { &*root.as_ptr() })
+    }
+
+    /// Turn this back into an `Arena`, so more data can be allocated in it.
+    pub fn thaw(self) -> Arena<'a, S> {
+        self.arena
+    }
+}
+
+impl<'a, S, R> fmt::Debug for FrozenArena<'a, S, R>
+where
+    S: SlabSource,
+    R: for<'r> FrozenRoot<'r>,
+    for<'r> <R as FrozenRoot<'r>>::Root: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.with_root(|root| f.debug_struct("FrozenArena").field("root", &root).finish())
+    }
+}
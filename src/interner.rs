@@ -0,0 +1,299 @@
+use crate::source::InfallibleSource;
+use crate::{ArenaError, ArenaVec, RawArena};
+
+use core::alloc::Layout;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+use core::ptr;
+use core::slice;
+use core::str;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+/// A handle to a value stored in an [`Interner`].
+///
+/// Each distinct value is only stored once, so symbols are compared by
+/// address, in constant time.
+pub struct Symbol<'arena, T: ?Sized = str> {
+    value: &'arena T,
+}
+
+impl<'arena, T: ?Sized> Symbol<'arena, T> {
+    pub fn get(self) -> &'arena T {
+        self.value
+    }
+}
+
+impl<'arena> Symbol<'arena, str> {
+    pub fn as_str(self) -> &'arena str {
+        self.value
+    }
+}
+
+impl<'arena, T: ?Sized> Clone for Symbol<'arena, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'arena, T: ?Sized> Copy for Symbol<'arena, T> {}
+
+impl<'arena, T: ?Sized> PartialEq for Symbol<'arena, T> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.value, other.value)
+    }
+}
+
+impl<'arena, T: ?Sized> Eq for Symbol<'arena, T> {}
+
+impl<'arena, T: ?Sized> Hash for Symbol<'arena, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.value as *const T as *const u8).hash(state);
+    }
+}
+
+impl<'arena, T: ?Sized> Deref for Symbol<'arena, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'arena, T: ?Sized + fmt::Debug> fmt::Debug for Symbol<'arena, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.value, f)
+    }
+}
+
+impl<'arena, T: ?Sized + fmt::Display> fmt::Display for Symbol<'arena, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.value, f)
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Entry<'arena> {
+    hash: u64,
+    bytes: &'arena [u8],
+}
+
+/// FNV-1a, which is fast for the short keys typically being interned.
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// A deduplicating table of strings and byte slices, stored in an arena.
+///
+/// Both the interned contents and the hash table itself are allocated in the
+/// arena. Strings and byte slices share storage, so interning `"a"` and `b"a"`
+/// stores the contents once.
+pub struct Interner<'arena, A: RawArena + ?Sized> {
+    arena: &'arena A,
+    // Open-addressed table, whose length is zero or a power of two.
+    slots: ArenaVec<'arena, Option<Entry<'arena>>, A>,
+    len: usize,
+}
+
+impl<'arena, A: RawArena + ?Sized> Interner<'arena, A> {
+    pub fn new_in(arena: &'arena A) -> Self {
+        Interner {
+            arena,
+            slots: ArenaVec::new_in(arena),
+            len: 0,
+        }
+    }
+
+    /// The number of distinct values which have been interned.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, s: &str) -> Option<Symbol<'arena>> {
+        let bytes = self.get_bytes(s.as_bytes())?;
+        Some(Symbol {
+            value: unsafe { str::from_utf8_unchecked(bytes.value) },
+        })
+    }
+
+    pub fn get_bytes(&self, bytes: &[u8]) -> Option<Symbol<'arena, [u8]>> {
+        match self.find(hash_bytes(bytes), bytes) {
+            Ok(idx) => Some(Symbol {
+                value: self.slots[idx].unwrap().bytes,
+            }),
+            Err(_) => None,
+        }
+    }
+
+    pub fn try_intern(&mut self, s: &str) -> Result<Symbol<'arena>, ArenaError> {
+        let bytes = self.try_intern_bytes(s.as_bytes())?;
+        Ok(Symbol {
+            value: unsafe { str::from_utf8_unchecked(bytes.value) },
+        })
+    }
+
+    pub fn try_intern_bytes(&mut self, bytes: &[u8]) -> Result<Symbol<'arena, [u8]>, ArenaError> {
+        let hash = hash_bytes(bytes);
+        if let Ok(idx) = self.find(hash, bytes) {
+            return Ok(Symbol {
+                value: self.slots[idx].unwrap().bytes,
+            });
+        }
+
+        // Keep the load factor at or below 3/4.
+        if (self.len + 1) * 4 > self.slots.len() * 3 {
+            self.try_rehash()?;
+        }
+
+        let stored = unsafe {
+            let ptr = self.arena.try_alloc_raw(Layout::for_value(bytes))?;
+            ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.as_ptr(), bytes.len());
+            slice::from_raw_parts(ptr.as_ptr(), bytes.len())
+        };
+        let idx = match self.find(hash, bytes) {
+            Ok(idx) | Err(idx) => idx,
+        };
+        self.slots[idx] = Some(Entry {
+            hash,
+            bytes: stored,
+        });
+        self.len += 1;
+        Ok(Symbol { value: stored })
+    }
+
+    /// Find the slot holding `bytes`, or the empty slot where it would be
+    /// inserted.
+    fn find(&self, hash: u64, bytes: &[u8]) -> Result<usize, usize> {
+        if self.slots.is_empty() {
+            return Err(0);
+        }
+        let mask = self.slots.len() - 1;
+        let mut idx = hash as usize & mask;
+        loop {
+            match self.slots[idx] {
+                Some(entry) if entry.hash == hash && entry.bytes == bytes => return Ok(idx),
+                Some(_) => idx = (idx + 1) & mask,
+                None => return Err(idx),
+            }
+        }
+    }
+
+    fn try_rehash(&mut self) -> Result<(), ArenaError> {
+        let new_len = if self.slots.is_empty() {
+            16
+        } else {
+            self.slots
+                .len()
+                .checked_mul(2)
+                .ok_or(ArenaError::LayoutOverflow)?
+        };
+        let mut slots = ArenaVec::try_with_capacity_in(new_len, self.arena)?;
+        for _ in 0..new_len {
+            slots.try_push(None)?;
+        }
+
+        let old = core::mem::replace(&mut self.slots, slots);
+        for entry in old.iter().flatten() {
+            let idx = match self.find(entry.hash, entry.bytes) {
+                Ok(idx) | Err(idx) => idx,
+            };
+            self.slots[idx] = Some(*entry);
+        }
+        Ok(())
+    }
+}
+
+impl<'arena, A> Interner<'arena, A>
+where
+    A: RawArena + ?Sized,
+    A::Source: InfallibleSource,
+{
+    pub fn intern(&mut self, s: &str) -> Symbol<'arena> {
+        A::Source::unwrap(self.try_intern(s), || Layout::for_value(s))
+    }
+
+    pub fn intern_bytes(&mut self, bytes: &[u8]) -> Symbol<'arena, [u8]> {
+        A::Source::unwrap(self.try_intern_bytes(bytes), || Layout::for_value(bytes))
+    }
+}
+
+impl<'arena, A: RawArena + ?Sized> fmt::Debug for Interner<'arena, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Interner").field("len", &self.len).finish()
+    }
+}
+
+/// An [`Interner`] which can be shared between threads, typically backed by a
+/// [`SyncArena`](crate::SyncArena).
+///
+/// *This type is only available when built with the `std` feature*
+#[cfg(feature = "std")]
+pub struct SyncInterner<'arena, A: RawArena + Sync + ?Sized> {
+    inner: std::sync::Mutex<Interner<'arena, A>>,
+}
+
+#[cfg(feature = "std")]
+impl<'arena, A: RawArena + Sync + ?Sized> SyncInterner<'arena, A> {
+    pub fn new_in(arena: &'arena A) -> Self {
+        SyncInterner {
+            inner: std::sync::Mutex::new(Interner::new_in(arena)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Interner<'arena, A>> {
+        match self.inner.lock() {
+            Ok(guard) => guard,
+            Err(e) => e.into_inner(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    pub fn get(&self, s: &str) -> Option<Symbol<'arena>> {
+        self.lock().get(s)
+    }
+
+    pub fn get_bytes(&self, bytes: &[u8]) -> Option<Symbol<'arena, [u8]>> {
+        self.lock().get_bytes(bytes)
+    }
+
+    pub fn try_intern(&self, s: &str) -> Result<Symbol<'arena>, ArenaError> {
+        self.lock().try_intern(s)
+    }
+
+    pub fn try_intern_bytes(&self, bytes: &[u8]) -> Result<Symbol<'arena, [u8]>, ArenaError> {
+        self.lock().try_intern_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'arena, A> SyncInterner<'arena, A>
+where
+    A: RawArena + Sync + ?Sized,
+    A::Source: InfallibleSource,
+{
+    pub fn intern(&self, s: &str) -> Symbol<'arena> {
+        self.lock().intern(s)
+    }
+
+    pub fn intern_bytes(&self, bytes: &[u8]) -> Symbol<'arena, [u8]> {
+        self.lock().intern_bytes(bytes)
+    }
+}
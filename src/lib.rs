@@ -4,12 +4,60 @@
 //! This module defines the [`Arena`], a
 
 #![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+// Arenas hand out unique references to fresh allocations from `&self`.
+#![allow(clippy::mut_from_ref, clippy::missing_safety_doc)]
 
+#[macro_use]
+mod sync;
+
+mod drops;
+mod epoch;
+mod error;
+mod frozen;
+mod interner;
+pub mod lock;
+mod raw_arena;
+mod rel;
 mod slab;
+mod snapshot;
 pub mod source;
+mod string;
+mod vec;
+
+pub use epoch::{EpochArena, EpochGuard};
+pub use error::ArenaError;
+pub use frozen::{FrozenArena, FrozenRoot};
+pub use interner::{Interner, Symbol};
+#[cfg(feature = "std")]
+pub use interner::SyncInterner;
+pub use raw_arena::RawArena;
+pub use rel::{ArenaOffset, RelPtr};
+pub use slab::{ArenaStats, Checkpoint};
+pub use snapshot::ArenaSnapshot;
+pub use string::ArenaString;
+pub use vec::ArenaVec;
+
+/// Format a string directly into an arena, like `format!`, returning a
+/// `&mut str` borrowed from the arena.
+///
+/// The first argument is a reference to the arena to allocate in.
+///
+/// # Panics
+///
+/// Panics if the arena fails to allocate the string.
+#[macro_export]
+macro_rules! arena_format {
+    ($arena:expr, $($arg:tt)*) => {{
+        let mut s = $crate::ArenaString::new_in($arena);
+        ::core::fmt::Write::write_fmt(&mut s, ::core::format_args!($($arg)*))
+            .expect("arena_format! failed to allocate");
+        s.into_str()
+    }};
+}
 
 macro_rules! arena_common {
-    ($Arena:ident) => {
+    ($Arena:ident $(<$L:ident: $LockBound:path>)? $(, $DropBound:path)?) => {
         #[cfg(any(feature = "alloc", feature = "std"))]
         impl<'a> $Arena<'a, $crate::source::AllocSource> {
             /// Create a new Arena with the default allocation strategy.
@@ -18,23 +66,228 @@ macro_rules! arena_common {
             }
         }
 
+        unsafe impl<'a, S: $crate::source::SlabSource $(, $L: $LockBound)?> $crate::RawArena
+            for $Arena<'a, S $(, $L)?>
+        {
+            type Source = S;
+
+            unsafe fn try_alloc_raw(
+                &self,
+                layout: Layout,
+            ) -> Result<NonNull<u8>, $crate::ArenaError> {
+                $Arena::try_alloc_raw(self, layout)
+            }
+
+            unsafe fn try_realloc_raw(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<u8>, $crate::ArenaError> {
+                $Arena::try_realloc_raw(self, ptr, old_layout, new_layout)
+            }
+        }
+
         impl<'a, S: $crate::source::SlabSource + Default> Default for $Arena<'a, S> {
             fn default() -> Self {
                 Self::with_source(Default::default())
             }
         }
 
-        impl<'a, S: $crate::source::SlabSource> $Arena<'a, S> {
-            pub fn with_source(source: S) -> Self {
-                $Arena {
-                    slab: Default::default(),
-                    source: source.into(),
-                    marker: PhantomData,
+        impl<'a, S: $crate::source::SlabSource $(, $L: $LockBound)?> $Arena<'a, S $(, $L)?> {
+            /// Free every allocation made in this arena, keeping its slabs
+            /// around to be reused by later allocations. Destructors registered
+            /// by `alloc_drop` are run first.
+            ///
+            /// Taking `&mut self` ensures no references into the arena are
+            /// still live.
+            pub fn reset(&mut self) {
+                unsafe { $crate::drops::run_drops(self.take_drops(), None) }
+                let slab = self.replace_slab(None);
+                unsafe { self.slabs_mut().recycle(slab) }
+            }
+
+            /// Like [`reset`](Self::reset), but returns every slab except the
+            /// largest to the `SlabSource`.
+            pub fn reset_and_shrink(&mut self) {
+                self.reset();
+                unsafe { self.slabs_mut().release_all_but_largest() }
+            }
+
+            /// Report how much memory this arena is holding, and how it is
+            /// being used.
+            pub fn stats(&self) -> $crate::ArenaStats {
+                self.with_slabs(|slabs, head| unsafe { slabs.stats(head) })
+            }
+
+            /// Iterate over the used part of each slab in the arena. The
+            /// arena's regular slabs come first, oldest first, followed by the
+            /// slabs holding large allocations. The segments are therefore not
+            /// in allocation order, and data in one segment doesn't continue
+            /// from the end of the previous one.
+            ///
+            /// Taking `&mut self` ensures nothing is writing to the arena.
+            ///
+            /// # Safety
+            ///
+            /// Every byte in the used part of a slab is read, so must be
+            /// initialized. The arena zeroes the bytes it leaves unwritten
+            /// itself:
+            ///
+            /// - padding skipped to align an allocation,
+            /// - space reserved for a destructor record which ended up unused,
+            ///   because allocating the value failed or it didn't need one,
+            /// - slots an `alloc_from_iter` iterator was too short to fill,
+            /// - the rest of a `LocalHandle`'s chunk, when it moves to a new
+            ///   chunk or is dropped.
+            ///
+            /// Everything else is up to the caller. Values in the arena must
+            /// not contain padding or other uninitialized bytes, memory from
+            /// `alloc_raw` must have been written in full, and `ArenaVec`s
+            /// must not hold spare capacity which couldn't be given back.
+            pub unsafe fn snapshot(&mut self) -> impl Iterator<Item = &[u8]> + '_ {
+                self.with_slabs(|slabs, head| slabs.segments(head))
+            }
+
+            /// Write the contents of the arena to `w`, in a format which can
+            /// be loaded with [`ArenaSnapshot::from_bytes`](crate::ArenaSnapshot::from_bytes).
+            ///
+            /// *This method is only available when built with the `std` feature*
+            ///
+            /// # Safety
+            ///
+            /// Every byte allocated from the arena must be initialized, as for
+            /// [`snapshot`](Self::snapshot).
+            #[cfg(feature = "std")]
+            pub unsafe fn write_snapshot<W: $crate::snapshot::io::Write>(
+                &mut self,
+                w: W,
+            ) -> $crate::snapshot::io::Result<()> {
+                let segments = self.with_slabs(|slabs, head| slabs.segments(head));
+                $crate::snapshot::write_snapshot(segments, w)
+            }
+
+            /// Record the current position of the arena, so it can later be
+            /// rolled back with [`rewind`](Self::rewind).
+            pub fn checkpoint(&self) -> $crate::Checkpoint {
+                let drops = self.current_drops();
+                self.with_slabs(|slabs, slab| unsafe { $crate::Checkpoint::new(slabs, slab, drops) })
+            }
+
+            /// Free every allocation made since `checkpoint` was taken, running
+            /// any destructors registered since then. Slabs added since then
+            /// are kept around to be reused.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `checkpoint` was not taken from this arena.
+            pub fn rewind(&mut self, checkpoint: $crate::Checkpoint) {
+                let drops = self.take_drops();
+                unsafe { $crate::drops::run_drops(drops, checkpoint.drops()) }
+                self.replace_drops(checkpoint.drops());
+
+                let slab = self.replace_slab(None);
+                let slab = unsafe { self.slabs_mut().rewind(slab, checkpoint) };
+                self.replace_slab(slab);
+            }
+
+            /// Run `f` with a borrow of this arena, freeing everything it
+            /// allocated when it returns.
+            ///
+            /// Allocations made within `f` borrow from the sub-arena passed to
+            /// it, so cannot escape the scope.
+            pub fn scope<R>(&mut self, f: impl FnOnce(&Self) -> R) -> R {
+                struct Rewind<'r, 'a, S: $crate::source::SlabSource $(, $L: $LockBound)?> {
+                    arena: &'r mut $Arena<'a, S $(, $L)?>,
+                    checkpoint: $crate::Checkpoint,
                 }
+
+                impl<'r, 'a, S: $crate::source::SlabSource $(, $L: $LockBound)?> Drop
+                    for Rewind<'r, 'a, S $(, $L)?>
+                {
+                    fn drop(&mut self) {
+                        self.arena.rewind(self.checkpoint);
+                    }
+                }
+
+                let checkpoint = self.checkpoint();
+                let rewind = Rewind {
+                    arena: self,
+                    checkpoint,
+                };
+                f(rewind.arena)
             }
         }
 
-        impl<'a, S: $crate::source::InfallibleSource> $Arena<'a, S> {
+        #[cfg(all(feature = "mmap", feature = "std", target_os = "linux"))]
+        impl<'a $(, $L: $LockBound)?> $Arena<'a, $crate::source::FileSource $(, $L)?> {
+            /// Record the location of `root` in the file header, so it can be
+            /// found with `MappedFile::root` after the file is reopened.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `root` was not allocated from this arena.
+            pub fn set_root<T: ?Sized>(&self, root: &T) {
+                let root = root as *const T as *const u8;
+                self.with_slabs(|slabs, _| slabs.source.set_root(root))
+            }
+        }
+
+        impl<'a, S: $crate::source::ContiguousSource $(, $L: $LockBound)?> $Arena<'a, S $(, $L)?> {
+            /// Get the offset of `value` from the start of the source's region.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `value` was not allocated from this arena.
+            pub fn offset_of<T>(&self, value: &T) -> $crate::ArenaOffset<T> {
+                let (base, len) = self.with_slabs(|slabs, _| slabs.source.region());
+                let offset = (value as *const T as usize).wrapping_sub(base.as_ptr() as usize);
+                assert!(
+                    offset.checked_add(core::mem::size_of::<T>()) <= Some(len),
+                    "value was not allocated from this arena"
+                );
+                $crate::ArenaOffset::from_offset(offset)
+            }
+
+            /// Allocate `value`, returning its offset rather than a reference.
+            pub fn try_alloc_offset<T: Copy + 'a>(
+                &self,
+                value: T,
+            ) -> Result<$crate::ArenaOffset<T>, $crate::ArenaError> {
+                let value = self.try_alloc(value)?;
+                Ok(self.offset_of(value))
+            }
+
+            /// Get a reference to the value at `offset`.
+            ///
+            /// # Safety
+            ///
+            /// `offset` must refer to a `T` allocated in this arena.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `offset` is not within the source's region.
+            pub unsafe fn resolve<T>(&self, offset: $crate::ArenaOffset<T>) -> &T {
+                let (base, len) = self.with_slabs(|slabs, _| slabs.source.region());
+                assert!(
+                    offset.offset().checked_add(core::mem::size_of::<T>()) <= Some(len),
+                    "offset is out of bounds"
+                );
+                &*base.as_ptr().add(offset.offset()).cast::<T>()
+            }
+        }
+
+        impl<'a, S $(, $L: $LockBound)?> $Arena<'a, S $(, $L)?>
+        where
+            S: $crate::source::ContiguousSource + $crate::source::InfallibleSource,
+        {
+            /// Allocate `value`, returning its offset rather than a reference.
+            pub fn alloc_offset<T: Copy + 'a>(&self, value: T) -> $crate::ArenaOffset<T> {
+                S::unwrap(self.try_alloc_offset(value), Layout::new::<T>)
+            }
+        }
+
+        impl<'a, S: $crate::source::InfallibleSource $(, $L: $LockBound)?> $Arena<'a, S $(, $L)?> {
             pub fn alloc<T: Copy + 'a>(&self, t: T) -> &mut T {
                 self.alloc_no_drop(t)
             }
@@ -43,6 +296,10 @@ macro_rules! arena_common {
                 S::unwrap(self.try_alloc_slice(t), || Layout::for_value(t))
             }
 
+            pub fn alloc_str<'s>(&'s self, s: &str) -> &'s mut str {
+                S::unwrap(self.try_alloc_str(s), || Layout::for_value(s))
+            }
+
             pub fn alloc_from_iter<I>(&self, iter: I, len: usize) -> &mut [I::Item]
             where
                 I: core::iter::IntoIterator,
@@ -55,6 +312,24 @@ macro_rules! arena_common {
                 self.alloc_with_no_drop(f)
             }
 
+            /// Allocate `t`, registering its destructor to be run when the
+            /// arena is dropped.
+            pub fn alloc_drop<T: 'a $(+ $DropBound)?>(&self, t: T) -> &mut T {
+                S::unwrap(self.try_alloc_drop(t), || Layout::new::<T>())
+            }
+
+            pub fn alloc_from_iter_drop<I>(&self, iter: I, len: usize) -> &mut [I::Item]
+            where
+                I: core::iter::IntoIterator,
+                I::Item: 'a $(+ $DropBound)?,
+            {
+                S::unwrap(self.try_alloc_from_iter_drop(iter, len), || {
+                    let item_layout = Layout::new::<I::Item>();
+                    let size = item_layout.size().saturating_mul(len);
+                    Layout::from_size_align(size, item_layout.align()).unwrap_or(item_layout)
+                })
+            }
+
             pub fn alloc_no_drop<T: 'a>(&self, t: T) -> &mut T {
                 S::unwrap(self.try_alloc_no_drop(t), || Layout::new::<T>())
             }
@@ -88,14 +363,26 @@ macro_rules! arena_common {
             pub unsafe fn alloc_raw(&self, layout: Layout) -> NonNull<u8> {
                 S::unwrap(self.try_alloc_raw(layout), || layout)
             }
+
+            pub unsafe fn realloc_raw(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> NonNull<u8> {
+                S::unwrap(self.try_realloc_raw(ptr, old_layout, new_layout), || new_layout)
+            }
         }
 
-        impl<'a, S: $crate::source::SlabSource> $Arena<'a, S> {
-            pub fn try_alloc<T: Copy + 'a>(&self, t: T) -> Option<&mut T> {
+        impl<'a, S: $crate::source::SlabSource $(, $L: $LockBound)?> $Arena<'a, S $(, $L)?> {
+            pub fn try_alloc<T: Copy + 'a>(&self, t: T) -> Result<&mut T, $crate::ArenaError> {
                 self.try_alloc_no_drop(t)
             }
 
-            pub fn try_alloc_slice<'s, T: Copy + 'a>(&'s self, t: &[T]) -> Option<&'s mut [T]> {
+            pub fn try_alloc_slice<'s, T: Copy + 'a>(
+                &'s self,
+                t: &[T],
+            ) -> Result<&'s mut [T], $crate::ArenaError> {
                 let layout = Layout::for_value(t);
                 unsafe {
                     self.try_alloc_init_no_drop(
@@ -110,7 +397,16 @@ macro_rules! arena_common {
                 }
             }
 
-            pub fn try_alloc_from_iter<I>(&self, iter: I, len: usize) -> Option<&mut [I::Item]>
+            pub fn try_alloc_str<'s>(&'s self, s: &str) -> Result<&'s mut str, $crate::ArenaError> {
+                let bytes = self.try_alloc_slice(s.as_bytes())?;
+                unsafe { Ok(core::str::from_utf8_unchecked_mut(bytes)) }
+            }
+
+            pub fn try_alloc_from_iter<I>(
+                &self,
+                iter: I,
+                len: usize,
+            ) -> Result<&mut [I::Item], $crate::ArenaError>
             where
                 I: core::iter::IntoIterator,
                 I::Item: Copy + 'a,
@@ -118,22 +414,120 @@ macro_rules! arena_common {
                 self.try_alloc_from_iter_no_drop(iter, len)
             }
 
-            pub fn try_alloc_with<T: Copy + 'a>(&self, f: impl FnOnce() -> T) -> Option<&mut T> {
+            pub fn try_alloc_with<T: Copy + 'a>(
+                &self,
+                f: impl FnOnce() -> T,
+            ) -> Result<&mut T, $crate::ArenaError> {
                 self.try_alloc_with_no_drop(f)
             }
 
-            pub fn try_alloc_no_drop<T: 'a>(&self, t: T) -> Option<&mut T> {
+            pub fn try_alloc_drop<T: 'a $(+ $DropBound)?>(
+                &self,
+                t: T,
+            ) -> Result<&mut T, $crate::ArenaError> {
+                unsafe {
+                    let record = self.try_alloc_drop_record::<T>()?;
+                    let value = match self.try_alloc_no_drop(t) {
+                        Ok(value) => value,
+                        Err(err) => {
+                            self.discard_drop_record(record);
+                            return Err(err);
+                        }
+                    };
+                    self.register_drop(record, NonNull::from(&mut *value), 1);
+                    Ok(value)
+                }
+            }
+
+            pub fn try_alloc_from_iter_drop<I>(
+                &self,
+                iter: I,
+                len: usize,
+            ) -> Result<&mut [I::Item], $crate::ArenaError>
+            where
+                I: core::iter::IntoIterator,
+                I::Item: 'a $(+ $DropBound)?,
+            {
+                unsafe {
+                    let record = self.try_alloc_drop_record::<I::Item>()?;
+                    let slice = match self.try_alloc_from_iter_no_drop(iter, len) {
+                        Ok(slice) => slice,
+                        Err(err) => {
+                            self.discard_drop_record(record);
+                            return Err(err);
+                        }
+                    };
+                    let ptr = NonNull::new_unchecked(slice.as_mut_ptr());
+                    self.register_drop(record, ptr, slice.len());
+                    Ok(slice)
+                }
+            }
+
+            /// Reserve space for a `DropRecord`, if dropping `T` has any effect.
+            unsafe fn try_alloc_drop_record<T>(
+                &self,
+            ) -> Result<Option<NonNull<$crate::drops::DropRecord>>, $crate::ArenaError> {
+                if !core::mem::needs_drop::<T>() {
+                    return Ok(None);
+                }
+                let layout = Layout::new::<$crate::drops::DropRecord>();
+                Ok(Some(self.try_alloc_raw(layout)?.cast()))
+            }
+
+            /// Give back a record from `try_alloc_drop_record` which won't be
+            /// used, as allocating the value failed. If something else was
+            /// allocated after it, it is zeroed instead, as it is still part
+            /// of the arena's used memory, which must be initialized.
+            unsafe fn discard_drop_record(
+                &self,
+                record: Option<NonNull<$crate::drops::DropRecord>>,
+            ) {
+                if let Some(record) = record {
+                    let size = core::mem::size_of::<$crate::drops::DropRecord>();
+                    if !self.resize_in_place(record.cast(), size, 0) {
+                        ptr::write_bytes(record.as_ptr(), 0, 1);
+                    }
+                }
+            }
+
+            unsafe fn register_drop<T>(
+                &self,
+                record: Option<NonNull<$crate::drops::DropRecord>>,
+                ptr: NonNull<T>,
+                len: usize,
+            ) {
+                let value = $crate::drops::DropRecord::new(ptr, len);
+                match (record, value) {
+                    (Some(record), Some(value)) => {
+                        ptr::write(record.as_ptr(), value);
+                        self.push_drop(record);
+                    }
+                    // The record is unused, but is still part of the arena's
+                    // used memory, which must be initialized.
+                    (Some(record), None) => ptr::write_bytes(record.as_ptr(), 0, 1),
+                    (None, _) => {}
+                }
+            }
+
+            pub fn try_alloc_no_drop<T: 'a>(&self, t: T) -> Result<&mut T, $crate::ArenaError> {
                 self.try_alloc_with_no_drop(|| t)
             }
 
-            pub fn try_alloc_from_iter_no_drop<I>(&self, iter: I, len: usize) -> Option<&mut [I::Item]>
+            pub fn try_alloc_from_iter_no_drop<I>(
+                &self,
+                iter: I,
+                len: usize,
+            ) -> Result<&mut [I::Item], $crate::ArenaError>
             where
                 I: core::iter::IntoIterator,
                 I::Item: 'a,
             {
                 let item_layout = Layout::new::<I::Item>();
-                let size = item_layout.size().checked_mul(len)?;
-                let layout = Layout::from_size_align(size, item_layout.align()).ok()?;
+                let layout = item_layout
+                    .size()
+                    .checked_mul(len)
+                    .and_then(|size| Layout::from_size_align(size, item_layout.align()).ok())
+                    .ok_or($crate::ArenaError::LayoutOverflow)?;
                 unsafe {
                     self.try_alloc_init_no_drop(
                         |ptr| {
@@ -148,6 +542,9 @@ macro_rules! arena_common {
                                 }
                                 idx += 1;
                             }
+                            // Zero the slots the iterator didn't fill, as they
+                            // are still part of the arena's used memory.
+                            ptr::write_bytes(dst.add(idx), 0, len - idx);
                             core::slice::from_raw_parts(dst, idx).into()
                         },
                         layout,
@@ -155,7 +552,10 @@ macro_rules! arena_common {
                 }
             }
 
-            pub fn try_alloc_with_no_drop<T: 'a>(&self, f: impl FnOnce() -> T) -> Option<&mut T> {
+            pub fn try_alloc_with_no_drop<T: 'a>(
+                &self,
+                f: impl FnOnce() -> T,
+            ) -> Result<&mut T, $crate::ArenaError> {
                 unsafe {
                     self.try_alloc_init_no_drop(
                         |p| {
@@ -167,13 +567,41 @@ macro_rules! arena_common {
                 }
             }
 
+            /// Resize the block at `ptr`, which must have been allocated from
+            /// this arena with `old_layout`, to fit `new_layout`.
+            ///
+            /// If the block is the most recent allocation in the current slab,
+            /// this only moves the slab's bump head, and the block stays in
+            /// place. Shrinking is also always done in place. Otherwise a new
+            /// block is allocated, and the contents are copied into it.
+            ///
+            /// On success the old block must no longer be used, even if the
+            /// same pointer was returned.
+            pub unsafe fn try_realloc_raw(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<u8>, $crate::ArenaError> {
+                let (old_size, new_size) = (old_layout.size(), new_layout.size());
+                if ptr.as_ptr().align_offset(new_layout.align()) == 0
+                    && (self.resize_in_place(ptr, old_size, new_size) || new_size <= old_size)
+                {
+                    return Ok(ptr);
+                }
+
+                let new = self.try_alloc_raw(new_layout)?;
+                ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr(), old_size.min(new_size));
+                Ok(new)
+            }
+
             pub unsafe fn try_alloc_init_no_drop<T: ?Sized + 'a>(
                 &self,
                 init: impl FnOnce(NonNull<u8>) -> NonNull<T>,
                 layout: Layout,
-            ) -> Option<&mut T> {
+            ) -> Result<&mut T, $crate::ArenaError> {
                 let ptr = self.try_alloc_raw(layout)?;
-                Some(&mut *init(ptr).as_ptr())
+                Ok(&mut *init(ptr).as_ptr())
             }
         }
     };
@@ -182,10 +610,15 @@ macro_rules! arena_common {
 mod arena;
 pub use arena::Arena;
 
-#[cfg(feature = "std")]
+#[cfg(any(feature = "allocator_api", feature = "allocator-api2"))]
+mod allocator;
+
 mod sync_arena;
-#[cfg(feature = "std")]
 pub use sync_arena::SyncArena;
 
-#[cfg(all(test, feature = "std"))]
+mod local_handle;
+pub use local_handle::LocalHandle;
+
+// Atomics can only be used within a model under loom, which has its own tests.
+#[cfg(all(test, feature = "std", not(loom)))]
 mod test;
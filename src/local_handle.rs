@@ -0,0 +1,213 @@
+use crate::lock::{DefaultLock, RawLock};
+use crate::slab::{zero_padding, SlabHeader};
+use crate::source::{InfallibleSource, SlabSource};
+use crate::{ArenaError, RawArena, SyncArena};
+
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::cmp;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use core::str;
+
+/// A handle for allocating from a [`SyncArena`] on one thread, created by
+/// [`SyncArena::local_handle`].
+///
+/// Rather than bumping the arena's shared slab for every allocation, the
+/// handle reserves a chunk of the slab at a time, and allocates from it
+/// without atomics. Any part of the chunk which is unused when the handle is
+/// dropped is wasted, and is counted as allocated by `stats`.
+///
+/// Allocations made through the handle live as long as the arena, not the
+/// handle.
+pub struct LocalHandle<'h, 'a, S: SlabSource, L: RawLock = DefaultLock> {
+    arena: &'h SyncArena<'a, S, L>,
+    // The unused part of the current chunk.
+    start: Cell<*mut u8>,
+    end: Cell<*mut u8>,
+    chunk_size: Cell<usize>,
+    // If the chunk size is chosen by the handle, the arena slab it was sized
+    // for.
+    sized_for: Option<Cell<*mut SlabHeader>>,
+    // Not `Send` or `Sync`, as the chunk is used without synchronization.
+    marker: PhantomData<*mut u8>,
+}
+
+impl<'a, S: SlabSource, L: RawLock> SyncArena<'a, S, L> {
+    /// Create a handle for allocating from this arena on the current thread.
+    ///
+    /// The handle reserves an eighth of the arena's current slab at a time,
+    /// staying below the source's large threshold, so that each chunk is
+    /// taken from the shared slab with a single atomic operation.
+    pub fn local_handle(&self) -> LocalHandle<'_, 'a, S, L> {
+        let mut handle = self.local_handle_with_chunk_size(0);
+        handle.sized_for = Some(Cell::new(NonNull::dangling().as_ptr()));
+        handle
+    }
+
+    /// Like [`local_handle`](Self::local_handle), but reserving
+    /// `chunk_size` bytes of the arena at a time.
+    pub fn local_handle_with_chunk_size(&self, chunk_size: usize) -> LocalHandle<'_, 'a, S, L> {
+        LocalHandle {
+            arena: self,
+            start: Cell::new(NonNull::dangling().as_ptr()),
+            end: Cell::new(NonNull::dangling().as_ptr()),
+            chunk_size: Cell::new(chunk_size),
+            sized_for: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'h, 'a, S: SlabSource, L: RawLock> LocalHandle<'h, 'a, S, L> {
+    pub fn arena(&self) -> &'h SyncArena<'a, S, L> {
+        self.arena
+    }
+
+    pub unsafe fn try_alloc_raw(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError> {
+        let start = self.start.get();
+        let padding = start.align_offset(layout.align());
+        let available = (self.end.get() as usize).wrapping_sub(start as usize);
+        if padding <= available && layout.size() <= available - padding {
+            let ptr = NonNull::new_unchecked(start.add(padding));
+            zero_padding(ptr, padding);
+            self.start.set(ptr.as_ptr().add(layout.size()));
+            return Ok(ptr);
+        }
+
+        self.try_alloc_raw_slow(layout)
+    }
+
+    #[inline(never)]
+    unsafe fn try_alloc_raw_slow(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError> {
+        // Allocations which would use up much of a chunk are made directly in
+        // the arena, so that the rest of the current chunk isn't wasted.
+        let chunk_size = self.chunk_size();
+        if layout.size() > chunk_size / 4 {
+            return self.arena.try_alloc_raw(layout);
+        }
+
+        let chunk_layout = Layout::from_size_align(chunk_size, layout.align())
+            .map_err(|_| ArenaError::LayoutOverflow)?;
+        let chunk = self.arena.try_alloc_raw(chunk_layout)?.as_ptr();
+        self.zero_rest_of_chunk();
+        self.start.set(chunk.add(layout.size()));
+        self.end.set(chunk.add(chunk_size));
+        Ok(NonNull::new_unchecked(chunk))
+    }
+
+    /// The size of chunk to reserve next. Unless it was given when the handle
+    /// was created, this is recomputed whenever the arena moves on to a new
+    /// slab, which takes the arena's lock.
+    fn chunk_size(&self) -> usize {
+        if let Some(sized_for) = &self.sized_for {
+            if sized_for.get() != self.arena.current_slab_ptr() {
+                let (slab, size) = self.arena.local_chunk_size();
+                sized_for.set(slab);
+                self.chunk_size.set(size);
+            }
+        }
+        self.chunk_size.get()
+    }
+
+    /// Zero the unused part of the current chunk before it is abandoned, as it
+    /// is part of the arena's used memory, which must be initialized.
+    fn zero_rest_of_chunk(&self) {
+        let (start, end) = (self.start.get(), self.end.get());
+        unsafe { ptr::write_bytes(start, 0, end as usize - start as usize) }
+    }
+
+    /// Resize a block previously allocated from this handle. Like
+    /// `SyncArena::try_realloc_raw`, this is done in place if the block is
+    /// the most recent allocation in the current chunk.
+    pub unsafe fn try_realloc_raw(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, ArenaError> {
+        let (old_size, new_size) = (old_layout.size(), new_layout.size());
+        if ptr.as_ptr().align_offset(new_layout.align()) == 0 {
+            let end = ptr.as_ptr().add(old_size);
+            let available = (self.end.get() as usize).wrapping_sub(ptr.as_ptr() as usize);
+            if end == self.start.get() && new_size <= available {
+                self.start.set(ptr.as_ptr().add(new_size));
+                return Ok(ptr);
+            }
+            if new_size <= old_size {
+                return Ok(ptr);
+            }
+        }
+
+        let new = self.try_alloc_raw(new_layout)?;
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr(), cmp::min(old_size, new_size));
+        Ok(new)
+    }
+
+    pub fn try_alloc<T: Copy + 'a>(&self, t: T) -> Result<&'h mut T, ArenaError> {
+        unsafe {
+            let ptr = self.try_alloc_raw(Layout::new::<T>())?.cast::<T>();
+            ptr::write(ptr.as_ptr(), t);
+            Ok(&mut *ptr.as_ptr())
+        }
+    }
+
+    pub fn try_alloc_slice<T: Copy + 'a>(&self, t: &[T]) -> Result<&'h mut [T], ArenaError> {
+        unsafe {
+            let ptr = self.try_alloc_raw(Layout::for_value(t))?.cast::<T>();
+            ptr::copy_nonoverlapping(t.as_ptr(), ptr.as_ptr(), t.len());
+            Ok(core::slice::from_raw_parts_mut(ptr.as_ptr(), t.len()))
+        }
+    }
+
+    pub fn try_alloc_str(&self, s: &str) -> Result<&'h mut str, ArenaError> {
+        let bytes = self.try_alloc_slice(s.as_bytes())?;
+        Ok(unsafe { str::from_utf8_unchecked_mut(bytes) })
+    }
+}
+
+impl<'h, 'a, S: InfallibleSource, L: RawLock> LocalHandle<'h, 'a, S, L> {
+    pub fn alloc<T: Copy + 'a>(&self, t: T) -> &'h mut T {
+        S::unwrap(self.try_alloc(t), Layout::new::<T>)
+    }
+
+    pub fn alloc_slice<T: Copy + 'a>(&self, t: &[T]) -> &'h mut [T] {
+        S::unwrap(self.try_alloc_slice(t), || Layout::for_value(t))
+    }
+
+    pub fn alloc_str(&self, s: &str) -> &'h mut str {
+        S::unwrap(self.try_alloc_str(s), || Layout::for_value(s))
+    }
+}
+
+unsafe impl<'h, 'a, S: SlabSource, L: RawLock> RawArena for LocalHandle<'h, 'a, S, L> {
+    type Source = S;
+
+    unsafe fn try_alloc_raw(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError> {
+        LocalHandle::try_alloc_raw(self, layout)
+    }
+
+    unsafe fn try_realloc_raw(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, ArenaError> {
+        LocalHandle::try_realloc_raw(self, ptr, old_layout, new_layout)
+    }
+}
+
+impl<'h, 'a, S: SlabSource, L: RawLock> Drop for LocalHandle<'h, 'a, S, L> {
+    fn drop(&mut self) {
+        self.zero_rest_of_chunk();
+    }
+}
+
+impl<'h, 'a, S: SlabSource, L: RawLock> fmt::Debug for LocalHandle<'h, 'a, S, L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LocalHandle")
+            .field("chunk_size", &self.chunk_size.get())
+            .finish()
+    }
+}
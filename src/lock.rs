@@ -0,0 +1,176 @@
+//! Locks used by [`SyncArena`](crate::SyncArena) to serialize access to its
+//! [`SlabSource`](crate::source::SlabSource).
+//!
+//! New slabs are installed without holding the lock, so it is only taken when
+//! the arena needs to talk to its source, or walk its slab lists. The lock
+//! type is chosen with the arena's `L` parameter, which defaults to
+//! [`DefaultLock`].
+
+use crate::sync::{spin_loop, AtomicBool, Ordering};
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+/// A mutual exclusion primitive which doesn't protect any data itself.
+///
+/// # Safety
+///
+/// Once `lock` has returned, no other call to `lock` may return until
+/// `unlock` has been called. `lock` must synchronize with the previous
+/// `unlock`, as if by an `Acquire` load of a `Release` store.
+pub unsafe trait RawLock {
+    /// Block until the lock is acquired.
+    fn lock(&self);
+
+    /// Release the lock.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held by the current context.
+    unsafe fn unlock(&self);
+}
+
+/// A lock which busy-waits until it is acquired. Usable without `std`.
+#[derive(Debug, Default)]
+pub struct SpinLock {
+    locked: AtomicBool,
+}
+
+impl SpinLock {
+    const_fn! {
+        pub fn new() -> Self {
+            SpinLock {
+                locked: AtomicBool::new(false),
+            }
+        }
+    }
+}
+
+unsafe impl RawLock for SpinLock {
+    fn lock(&self) {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Wait for the lock to look free before trying to take it again,
+            // so the cache line isn't bounced between waiting threads.
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+    }
+
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+/// A lock which parks waiting threads, built on `std::sync::Mutex`.
+///
+/// *This type is only available when built with the `std` feature*
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct StdLock {
+    locked: std::sync::Mutex<bool>,
+    unlocked: std::sync::Condvar,
+}
+
+#[cfg(feature = "std")]
+impl StdLock {
+    pub const fn new() -> Self {
+        StdLock {
+            locked: std::sync::Mutex::new(false),
+            unlocked: std::sync::Condvar::new(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, bool> {
+        // The mutex only guards a flag, which can't be left inconsistent.
+        self.locked.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "std")]
+unsafe impl RawLock for StdLock {
+    fn lock(&self) {
+        let mut locked = self.state();
+        while *locked {
+            locked = self.unlocked.wait(locked).unwrap_or_else(|e| e.into_inner());
+        }
+        *locked = true;
+    }
+
+    unsafe fn unlock(&self) {
+        *self.state() = false;
+        self.unlocked.notify_one();
+    }
+}
+
+/// The lock used by a `SyncArena` unless another is chosen: [`StdLock`] when
+/// built with the `std` feature, otherwise [`SpinLock`].
+#[cfg(feature = "std")]
+pub type DefaultLock = StdLock;
+
+/// The lock used by a `SyncArena` unless another is chosen: [`StdLock`] when
+/// built with the `std` feature, otherwise [`SpinLock`].
+#[cfg(not(feature = "std"))]
+pub type DefaultLock = SpinLock;
+
+/// A `T` protected by a `RawLock`.
+pub(crate) struct Lock<L, T> {
+    raw: L,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<L: Send, T: Send> Send for Lock<L, T> {}
+unsafe impl<L: Sync, T: Send> Sync for Lock<L, T> {}
+
+impl<L: RawLock, T> Lock<L, T> {
+    pub(crate) const fn new(raw: L, data: T) -> Self {
+        Lock {
+            raw,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub(crate) fn lock(&self) -> LockGuard<'_, L, T> {
+        self.raw.lock();
+        LockGuard { lock: self }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+pub(crate) struct LockGuard<'l, L: RawLock, T> {
+    lock: &'l Lock<L, T>,
+}
+
+impl<'l, L: RawLock, T> Deref for LockGuard<'l, L, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'l, L: RawLock, T> DerefMut for LockGuard<'l, L, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'l, L: RawLock, T> Drop for LockGuard<'l, L, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.unlock() }
+    }
+}
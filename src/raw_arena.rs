@@ -0,0 +1,24 @@
+use crate::source::SlabSource;
+use crate::ArenaError;
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+/// The raw allocation interface shared by the arena types. This allows
+/// collections like [`ArenaVec`](crate::ArenaVec) to allocate from any arena.
+pub unsafe trait RawArena {
+    type Source: SlabSource;
+
+    /// Allocate a block of memory with the given layout, or return why it
+    /// couldn't be allocated. See `Arena::try_alloc_raw`.
+    unsafe fn try_alloc_raw(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError>;
+
+    /// Resize a block previously allocated from this arena, in place if
+    /// possible. See `Arena::try_realloc_raw`.
+    unsafe fn try_realloc_raw(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, ArenaError>;
+}
@@ -0,0 +1,147 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+/// A pointer stored as an offset from its own address.
+///
+/// Data which links to itself with `RelPtr`s stays valid when it is moved as
+/// a whole, for example when an arena's buffer is copied, or a file written by
+/// a `FileSource` is mapped at a different address. Moving a `RelPtr` on its
+/// own invalidates it, so it should only be set once it is in its final
+/// location in the arena.
+#[repr(transparent)]
+pub struct RelPtr<T> {
+    // Zero is used for null, as a `RelPtr` can't point to itself.
+    offset: isize,
+    marker: PhantomData<*const T>,
+}
+
+unsafe impl<T: Sync> Send for RelPtr<T> {}
+unsafe impl<T: Sync> Sync for RelPtr<T> {}
+
+impl<T> RelPtr<T> {
+    pub const fn null() -> Self {
+        RelPtr {
+            offset: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.offset == 0
+    }
+
+    /// Point at `target`.
+    pub fn set(&mut self, target: &T) {
+        let offset = (target as *const T as isize).wrapping_sub(self as *const Self as isize);
+        assert!(offset != 0, "RelPtr cannot point to itself");
+        self.offset = offset;
+    }
+
+    pub fn clear(&mut self) {
+        self.offset = 0;
+    }
+
+    /// Get a raw pointer to the target, or null.
+    pub fn as_ptr(&self) -> *const T {
+        if self.is_null() {
+            return ptr::null();
+        }
+        (self as *const Self as *const u8).wrapping_offset(self.offset) as *const T
+    }
+
+    /// Get a reference to the target.
+    ///
+    /// # Safety
+    ///
+    /// The target must have been moved along with this pointer since it was
+    /// set, and still be valid.
+    pub unsafe fn get(&self) -> Option<&T> {
+        self.as_ptr().as_ref()
+    }
+}
+
+impl<T> Default for RelPtr<T> {
+    fn default() -> Self {
+        RelPtr::null()
+    }
+}
+
+impl<T> fmt::Debug for RelPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RelPtr").field(&self.offset).finish()
+    }
+}
+
+/// The position of a `T` in an arena backed by a
+/// [`ContiguousSource`](crate::source::ContiguousSource), as an offset from the
+/// start of its region.
+///
+/// Unlike a [`RelPtr`], an `ArenaOffset` can be stored anywhere, but needs the
+/// start of the region to be resolved, with `Arena::resolve` or
+/// [`resolve_in`](Self::resolve_in).
+#[repr(transparent)]
+pub struct ArenaOffset<T> {
+    offset: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ArenaOffset<T> {
+    pub const fn from_offset(offset: usize) -> Self {
+        ArenaOffset {
+            offset,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn offset(self) -> usize {
+        self.offset
+    }
+
+    /// Resolve this offset within a copy of the arena's region, such as the
+    /// bytes of a `MappedFile`.
+    ///
+    /// # Safety
+    ///
+    /// `region` must hold a valid `T` at this offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `T` would not be within `region`, or would be misaligned.
+    pub unsafe fn resolve_in(self, region: &[u8]) -> &T {
+        assert!(
+            self.offset.checked_add(mem::size_of::<T>()) <= Some(region.len()),
+            "offset is out of bounds"
+        );
+        let ptr = region.as_ptr().add(self.offset);
+        assert_eq!(
+            ptr.align_offset(mem::align_of::<T>()),
+            0,
+            "offset is misaligned"
+        );
+        &*(ptr as *const T)
+    }
+}
+
+impl<T> Clone for ArenaOffset<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaOffset<T> {}
+
+impl<T> PartialEq for ArenaOffset<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T> Eq for ArenaOffset<T> {}
+
+impl<T> fmt::Debug for ArenaOffset<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ArenaOffset").field(&self.offset).finish()
+    }
+}
@@ -1,25 +1,409 @@
+use crate::drops::DropRecord;
 use crate::source::SlabSource;
+use crate::sync::{AtomicMut, AtomicUsize, Ordering};
+use crate::ArenaError;
 
 use core::alloc::Layout;
+use core::cmp;
+use core::marker::PhantomData;
 use core::mem;
 use core::ptr::{self, NonNull};
-use core::sync::atomic::AtomicUsize;
-
-#[cfg(feature = "std")]
-use core::sync::atomic::Ordering;
 
 #[repr(C)]
 pub(crate) struct SlabHeader {
     next: Option<NonNull<SlabHeader>>,
-    size: usize,
+    // The layout returned by `SlabSource::alloc_slab` or `grow_slab`, which is
+    // handed back unchanged to `dealloc_slab`. The size is atomic, as the slab
+    // may be grown while other threads are allocating from it.
+    size: AtomicUsize,
+    align: usize,
     used: AtomicUsize,
+    // Bytes within `used` which were skipped to align allocations.
+    padding: AtomicUsize,
+}
+
+impl SlabHeader {
+    fn new(next: Option<NonNull<SlabHeader>>, layout: Layout) -> Self {
+        SlabHeader {
+            next,
+            size: AtomicUsize::new(layout.size()),
+            align: layout.align(),
+            used: AtomicUsize::new(mem::size_of::<SlabHeader>()),
+            padding: AtomicUsize::new(0),
+        }
+    }
+
+    fn size(&self) -> usize {
+        // Pairs with the store in `try_grow`, so that the grown part of the
+        // slab is visible before it is allocated from.
+        self.size.load(Ordering::Acquire)
+    }
+
+    fn layout(&self) -> Layout {
+        unsafe { Layout::from_size_align_unchecked(self.size(), self.align) }
+    }
+
+    /// Mark every byte of the slab as unused.
+    fn clear(&mut self) {
+        self.used.store_mut(mem::size_of::<SlabHeader>());
+        self.padding.store_mut(0);
+    }
+}
+
+/// Memory usage statistics for an arena, returned by `stats`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// The number of slabs held by the arena, including spare slabs.
+    pub slab_count: usize,
+    /// The total size of every slab, including slab headers.
+    pub total_bytes: usize,
+    /// The size of the largest slab.
+    pub largest_slab: usize,
+    /// Bytes handed out to allocations.
+    pub allocated_bytes: usize,
+    /// Bytes skipped between allocations to satisfy alignment.
+    pub padding_bytes: usize,
+    /// Bytes left unused at the end of slabs which were full, and which will
+    /// not be allocated from again until the arena is reset.
+    pub abandoned_bytes: usize,
+    /// Bytes in spare slabs which are waiting to be reused.
+    pub spare_bytes: usize,
+}
+
+/// A saved position in an arena, which it can later be rewound to.
+///
+/// Created by `checkpoint`, and consumed by `rewind`.
+#[derive(Copy, Clone, Debug)]
+pub struct Checkpoint {
+    slab: Option<NonNull<SlabHeader>>,
+    used: usize,
+    padding: usize,
+    large: Option<NonNull<SlabHeader>>,
+    drops: Option<NonNull<DropRecord>>,
+}
+
+impl Checkpoint {
+    pub(crate) unsafe fn new<S>(
+        slabs: &Slabs<S>,
+        slab: Option<NonNull<SlabHeader>>,
+        drops: Option<NonNull<DropRecord>>,
+    ) -> Self {
+        let (used, padding) = match slab {
+            Some(slab) => (
+                slab.as_ref().used.load(Ordering::Relaxed),
+                slab.as_ref().padding.load(Ordering::Relaxed),
+            ),
+            None => (0, 0),
+        };
+        Checkpoint {
+            slab,
+            used,
+            padding,
+            large: slabs.large,
+            drops,
+        }
+    }
+
+    pub(crate) fn drops(&self) -> Option<NonNull<DropRecord>> {
+        self.drops
+    }
+}
+
+/// Slab lists which were detached from an arena by `Slabs::retire`.
+pub(crate) struct RetiredSlabs {
+    head: Option<NonNull<SlabHeader>>,
+    large: Option<NonNull<SlabHeader>>,
+}
+
+/// The slab source for an arena, along with any empty slabs which are being
+/// held on to for reuse. Arenas keep this behind their lock.
+pub(crate) struct Slabs<S> {
+    pub(crate) source: S,
+    spare: Option<NonNull<SlabHeader>>,
+    // Dedicated slabs for large allocations, which are kept out of the main
+    // list so that they don't replace the slab being bump allocated from.
+    large: Option<NonNull<SlabHeader>>,
+}
+
+// The spare list is exclusively owned by the `Slabs`, so it is as safe to send
+// as the source itself.
+unsafe impl<S: Send> Send for Slabs<S> {}
+
+impl<S: SlabSource> Slabs<S> {
+    pub(crate) const fn new(source: S) -> Self {
+        Slabs {
+            source,
+            spare: None,
+            large: None,
+        }
+    }
+
+    /// Rewind every slab in the list starting at `head`, as well as every
+    /// large slab, and move them onto the spare list to be handed out again by
+    /// `alloc_slow`.
+    pub(crate) unsafe fn recycle(&mut self, head: Option<NonNull<SlabHeader>>) {
+        self.recycle_until(head, None);
+        let large = self.large.take();
+        self.recycle_until(large, None);
+    }
+
+    /// Like `recycle`, but stops when `stop` is reached, leaving it and the
+    /// slabs after it in place. Returns `false` if `stop` was never found.
+    unsafe fn recycle_until(
+        &mut self,
+        mut ptr: Option<NonNull<SlabHeader>>,
+        stop: Option<NonNull<SlabHeader>>,
+    ) -> bool {
+        while ptr != stop {
+            let mut curr = match ptr {
+                Some(curr) => curr,
+                None => return false,
+            };
+            let header = curr.as_mut();
+            ptr = header.next;
+            header.clear();
+            header.next = self.spare;
+            self.spare = Some(curr);
+        }
+        true
+    }
+
+    /// Move `slab`, which was returned by `alloc_slow` but never became the
+    /// head of the list, onto the spare list.
+    pub(crate) unsafe fn recycle_unused(&mut self, slab: NonNull<SlabHeader>) {
+        let next = slab.as_ref().next;
+        self.recycle_until(Some(slab), next);
+    }
+
+    /// Detach the slab list starting at `head`, along with the large slabs, so
+    /// that the arena starts new lists. Allocations in the detached slabs stay
+    /// valid until they are passed to `recycle_retired`.
+    pub(crate) fn retire(&mut self, head: Option<NonNull<SlabHeader>>) -> RetiredSlabs {
+        RetiredSlabs {
+            head,
+            large: self.large.take(),
+        }
+    }
+
+    /// Move slabs detached by `retire` onto the spare list.
+    pub(crate) unsafe fn recycle_retired(&mut self, retired: RetiredSlabs) {
+        self.recycle_until(retired.head, None);
+        self.recycle_until(retired.large, None);
+    }
+
+    /// Roll the slab list starting at `head` back to the state recorded by
+    /// `checkpoint`, returning the new head of the list.
+    pub(crate) unsafe fn rewind(
+        &mut self,
+        head: Option<NonNull<SlabHeader>>,
+        checkpoint: Checkpoint,
+    ) -> Option<NonNull<SlabHeader>> {
+        let large = self.large.take();
+        if !self.recycle_until(head, checkpoint.slab)
+            || !self.recycle_until(large, checkpoint.large)
+        {
+            panic!("checkpoint was not taken from this arena");
+        }
+        self.large = checkpoint.large;
+        if let Some(mut slab) = checkpoint.slab {
+            // If the arena was already rewound past this checkpoint, `used`
+            // may be behind it, and must not be moved forwards.
+            let header = slab.as_mut();
+            if checkpoint.used < header.used.load_mut() {
+                header.used.store_mut(checkpoint.used);
+                header.padding.store_mut(checkpoint.padding);
+            }
+        }
+        checkpoint.slab
+    }
+
+    /// The size of the chunks `LocalHandle`s reserve from `head` by default:
+    /// an eighth of the slab, and below the source's large threshold, so that
+    /// chunks are bumped from the shared slab rather than each getting a
+    /// dedicated one.
+    pub(crate) unsafe fn local_chunk_size(&self, head: Option<NonNull<SlabHeader>>) -> usize {
+        let slab_size = head.map_or(0, |slab| slab.as_ref().size());
+        cmp::min(slab_size / 8, self.source.large_threshold() / 2)
+    }
+
+    /// Compute statistics for the slab list starting at `head`, and the spare
+    /// list. As other threads may be allocating, these may be out of date.
+    pub(crate) unsafe fn stats(&self, head: Option<NonNull<SlabHeader>>) -> ArenaStats {
+        let mut stats = ArenaStats::default();
+        for &list in &[head, self.large] {
+            let mut ptr = list;
+            while let Some(curr) = ptr {
+                let header = curr.as_ref();
+                let used = header.used.load(Ordering::Relaxed);
+                let padding = header.padding.load(Ordering::Relaxed);
+                // `padding` is bumped after `used`, so a concurrent reader may
+                // see padding for an allocation which isn't in `used` yet.
+                let data = used - mem::size_of::<SlabHeader>();
+                stats.allocated_bytes += data.saturating_sub(padding);
+                stats.padding_bytes += padding;
+                if ptr != head {
+                    stats.abandoned_bytes += header.size() - used;
+                }
+                stats.add_slab(header.size());
+                ptr = header.next;
+            }
+        }
+
+        let mut ptr = self.spare;
+        while let Some(curr) = ptr {
+            let header = curr.as_ref();
+            stats.spare_bytes += header.size();
+            stats.add_slab(header.size());
+            ptr = header.next;
+        }
+        stats
+    }
+
+    /// Iterate over the used part of each slab in the list starting at `head`,
+    /// followed by the large slabs, oldest first.
+    pub(crate) unsafe fn segments<'s>(&self, head: Option<NonNull<SlabHeader>>) -> Segments<'s> {
+        Segments {
+            lists: [head, self.large],
+            list: 0,
+            prev: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Return every spare slab other than the largest one to the source.
+    pub(crate) unsafe fn release_all_but_largest(&mut self) {
+        let mut largest: Option<NonNull<SlabHeader>> = None;
+        let mut ptr = self.spare.take();
+        while let Some(mut curr) = ptr {
+            ptr = curr.as_ref().next;
+            match largest {
+                Some(prev) if prev.as_ref().size() >= curr.as_ref().size() => {
+                    self.dealloc(curr);
+                }
+                _ => {
+                    if let Some(prev) = largest {
+                        self.dealloc(prev);
+                    }
+                    curr.as_mut().next = None;
+                    largest = Some(curr);
+                }
+            }
+        }
+        self.spare = largest;
+    }
+
+    /// Remove the smallest spare slab which can hold at least `min_size` bytes
+    /// from the spare list.
+    unsafe fn take_spare(&mut self, min_size: usize) -> Option<NonNull<SlabHeader>> {
+        let mut best: Option<NonNull<SlabHeader>> = None;
+        let mut ptr = self.spare;
+        while let Some(curr) = ptr {
+            let size = curr.as_ref().size();
+            let better = match best {
+                Some(best) => size < best.as_ref().size(),
+                None => true,
+            };
+            if size >= min_size && better {
+                best = Some(curr);
+            }
+            ptr = curr.as_ref().next;
+        }
+        let best = best?;
+
+        // Unlink the chosen slab from the spare list.
+        let mut link = &mut self.spare;
+        while let Some(mut curr) = *link {
+            if curr == best {
+                break;
+            }
+            link = &mut curr.as_mut().next;
+        }
+        *link = best.as_ref().next;
+        Some(best)
+    }
+
+    /// Ask the source to grow `head` so that `layout` fits, and allocate it
+    /// there. Other threads may still be allocating from `head`.
+    unsafe fn try_grow(
+        &mut self,
+        head: Option<NonNull<SlabHeader>>,
+        layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        let slab = head?;
+        let header = slab.as_ref();
+        let min_size = header
+            .used
+            .load(Ordering::Relaxed)
+            .checked_add(layout.align() - 1)?
+            .checked_add(layout.size())?;
+        let new_layout = self
+            .source
+            .grow_slab(slab.cast::<u8>(), header.layout(), min_size)?;
+        assert!(new_layout.size() >= min_size && new_layout.align() == header.align);
+        header.size.store(new_layout.size(), Ordering::Release);
+        alloc_in_slab_atomic(head, layout)
+    }
+
+    unsafe fn dealloc(&mut self, slab: NonNull<SlabHeader>) {
+        let layout = slab.as_ref().layout();
+        self.source.dealloc_slab(slab.cast::<u8>(), layout);
+    }
+}
+
+/// The iterator returned by `Slabs::segments`. Each segment starts at the
+/// same address as the slab's data, so keeps its alignment.
+#[derive(Clone)]
+pub(crate) struct Segments<'s> {
+    lists: [Option<NonNull<SlabHeader>>; 2],
+    list: usize,
+    // The slab last returned from the current list.
+    prev: Option<NonNull<SlabHeader>>,
+    marker: PhantomData<&'s [u8]>,
+}
+
+impl<'s> Iterator for Segments<'s> {
+    type Item = &'s [u8];
+
+    fn next(&mut self) -> Option<&'s [u8]> {
+        while let Some(&head) = self.lists.get(self.list) {
+            if self.prev == head {
+                self.list += 1;
+                self.prev = None;
+                continue;
+            }
+
+            // The lists are linked newest first, so find the slab before the
+            // one returned last. There are rarely many slabs, so this is fine.
+            unsafe {
+                let mut curr = head?;
+                while curr.as_ref().next != self.prev {
+                    curr = curr.as_ref().next?;
+                }
+                self.prev = Some(curr);
+                let start = mem::size_of::<SlabHeader>();
+                let used = curr.as_ref().used.load(Ordering::Relaxed);
+                let data = curr.cast::<u8>().as_ptr().add(start);
+                return Some(core::slice::from_raw_parts(data, used - start));
+            }
+        }
+        None
+    }
+}
+
+/// Zero the `padding` bytes skipped before `ptr` to align it, so that every
+/// byte in the used part of a slab is initialized, and can be read by
+/// `Arena::snapshot`.
+pub(crate) unsafe fn zero_padding(ptr: NonNull<u8>, padding: usize) {
+    if padding != 0 {
+        ptr::write_bytes(ptr.as_ptr().sub(padding), 0, padding);
+    }
 }
 
 unsafe fn alloc_in_slab_common(
     slab: NonNull<SlabHeader>,
     layout: Layout,
     used: usize,
-) -> Option<(usize, NonNull<u8>)> {
+) -> Option<(usize, usize, NonNull<u8>)> {
     // Current value for the allocation head.
     let start_ptr = slab.cast::<u8>().as_ptr().add(used);
 
@@ -30,12 +414,13 @@ unsafe fn alloc_in_slab_common(
     // Determine the value after the end of the new allocation.
     let next = used.checked_add(padding)?.checked_add(layout.size())?;
 
-    if next > slab.as_ref().size {
+    if next > slab.as_ref().size() {
         return None;
     }
     Some((
         next,
-        NonNull::new_unchecked(start_ptr.add(padding) as *mut u8),
+        padding,
+        NonNull::new_unchecked(start_ptr.add(padding)),
     ))
 }
 
@@ -47,13 +432,15 @@ pub(crate) unsafe fn alloc_in_slab_nonatomic(
 
     // When non-atomic, this method has exclusive access to the slab header. Use
     // this access to perform optimizable non-atomic loads.
-    let prev = *slab.as_mut().used.get_mut();
-    let (next, ptr) = alloc_in_slab_common(slab, layout, prev)?;
-    *slab.as_mut().used.get_mut() = next;
+    let prev = slab.as_mut().used.load_mut();
+    let (next, padding, ptr) = alloc_in_slab_common(slab, layout, prev)?;
+    zero_padding(ptr, padding);
+    slab.as_mut().used.store_mut(next);
+    let padding = slab.as_mut().padding.load_mut() + padding;
+    slab.as_mut().padding.store_mut(padding);
     Some(ptr)
 }
 
-#[cfg(feature = "std")]
 pub(crate) unsafe fn alloc_in_slab_atomic(
     slab: Option<NonNull<SlabHeader>>,
     layout: Layout,
@@ -62,63 +449,194 @@ pub(crate) unsafe fn alloc_in_slab_atomic(
 
     // Perform a CAS-loop over the `used` field from `SlabHeader`. We can use a
     // relaxed load for reads, as they'll be validated by the
-    // compare_exchange_weak, so it's OK to read an out-of-date value. The write
-    // doesn't actively protect any memory, so may also be `Relaxed`.
+    // compare_exchange_weak, so it's OK to read an out-of-date value. The
+    // header itself was published by the `Acquire` load of the arena's `slab`
+    // pointer.
     //
-    // XXX(nika): Get someone to double-check that `Relaxed` is OK here.
+    // The range handed out may have been given back by another thread
+    // shrinking or freeing the most recent allocation, which may still have
+    // been writing to it just before. A successful CAS is `Acquire`, pairing
+    // with the `Release` in `resize_in_slab_atomic`, so that those writes
+    // happen before the new owner's.
     let mut prev = slab.as_ref().used.load(Ordering::Relaxed);
     loop {
-        let (next, ptr) = alloc_in_slab_common(slab, layout, prev)?;
+        let (next, padding, ptr) = alloc_in_slab_common(slab, layout, prev)?;
 
         match slab.as_ref().used.compare_exchange_weak(
             prev,
             next,
-            Ordering::Relaxed,
+            Ordering::Acquire,
             Ordering::Relaxed,
         ) {
-            Ok(_) => return Some(ptr),
+            Ok(_) => {
+                zero_padding(ptr, padding);
+
+                // Padding is only tracked for statistics, so needs no ordering.
+                if padding != 0 {
+                    slab.as_ref().padding.fetch_add(padding, Ordering::Relaxed);
+                }
+                return Some(ptr);
+            }
             Err(next_prev) => prev = next_prev,
         }
     }
 }
 
+/// Compute the new value of `used` when resizing the `old_size` byte block at
+/// `ptr` to `new_size` bytes, if it ends at `used` and the result fits.
+unsafe fn resize_in_slab_common(
+    slab: NonNull<SlabHeader>,
+    ptr: NonNull<u8>,
+    old_size: usize,
+    new_size: usize,
+    used: usize,
+) -> Option<usize> {
+    let start = (ptr.as_ptr() as usize).wrapping_sub(slab.as_ptr() as usize);
+    if start.wrapping_add(old_size) != used {
+        return None;
+    }
+    let next = start.checked_add(new_size)?;
+    if next > slab.as_ref().size() {
+        return None;
+    }
+    Some(next)
+}
+
+/// Resize the most recent allocation in `slab` in place. Returns `false` if
+/// `ptr` is not the most recent allocation, or there is not enough space.
+pub(crate) unsafe fn resize_in_slab_nonatomic(
+    slab: Option<NonNull<SlabHeader>>,
+    ptr: NonNull<u8>,
+    old_size: usize,
+    new_size: usize,
+) -> bool {
+    let mut slab = match slab {
+        Some(slab) => slab,
+        None => return false,
+    };
+    let prev = slab.as_mut().used.load_mut();
+    match resize_in_slab_common(slab, ptr, old_size, new_size, prev) {
+        Some(next) => {
+            slab.as_mut().used.store_mut(next);
+            true
+        }
+        None => false,
+    }
+}
+
+pub(crate) unsafe fn resize_in_slab_atomic(
+    slab: Option<NonNull<SlabHeader>>,
+    ptr: NonNull<u8>,
+    old_size: usize,
+    new_size: usize,
+) -> bool {
+    let slab = match slab {
+        Some(slab) => slab,
+        None => return false,
+    };
+
+    // Unlike `alloc_in_slab_atomic`, there is no need to loop here, as if
+    // another thread bumped `used`, `ptr` is no longer the most recent
+    // allocation.
+    //
+    // Shrinking gives bytes back to be allocated by other threads, so the CAS
+    // releases this thread's writes to them. Growing takes bytes which may
+    // have been given back by another thread, so it also acquires.
+    let prev = slab.as_ref().used.load(Ordering::Relaxed);
+    match resize_in_slab_common(slab, ptr, old_size, new_size, prev) {
+        Some(next) => slab
+            .as_ref()
+            .used
+            .compare_exchange(prev, next, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok(),
+        None => false,
+    }
+}
+
+/// Allocate a new slab to hold `layout`, and perform the allocation in it.
+///
+/// Returns the new head of the slab list, which will still be `head` if the
+/// allocation was large enough to be given a dedicated slab.
 pub(crate) unsafe fn alloc_slow<S: SlabSource>(
-    source: &mut S,
+    slabs: &mut Slabs<S>,
     layout: Layout,
-    next: Option<NonNull<SlabHeader>>,
-) -> Option<(NonNull<SlabHeader>, NonNull<u8>)> {
+    head: Option<NonNull<SlabHeader>>,
+) -> Result<(Option<NonNull<SlabHeader>>, NonNull<u8>), ArenaError> {
     // Check if allocation must be larger than the required default size.
     // Required capacity must include the header, the size of the required
     // allocation object, and padding required to align to min_layout's
     // alignment.
     let padding = layout.align().saturating_sub(mem::align_of::<SlabHeader>());
     let min_size = mem::size_of::<SlabHeader>()
-        .checked_add(padding)?
-        .checked_add(layout.size())?;
+        .checked_add(padding)
+        .and_then(|size| size.checked_add(layout.size()))
+        .ok_or(ArenaError::LayoutOverflow)?;
+
+    // Large allocations are moved out of the way if there is a current slab
+    // which could still be used for smaller allocations.
+    let large = head.is_some() && layout.size() >= slabs.source.large_threshold();
+    let next = if large { slabs.large } else { head };
+
+    // If the source can extend the current slab in place, keep allocating
+    // from it rather than starting a new one.
+    if !large {
+        if let Some(ptr) = slabs.try_grow(head, layout) {
+            return Ok((head, ptr));
+        }
+    }
 
-    let alloc_layout = Layout::from_size_align(min_size, mem::align_of::<SlabHeader>()).ok()?;
+    let slab = match slabs.take_spare(min_size) {
+        Some(mut slab) => {
+            // Spare slabs were rewound when they were recycled, so only need
+            // to be linked into the list.
+            slab.as_mut().next = next;
+            slab
+        }
+        None => {
+            let alloc_layout = Layout::from_size_align(min_size, mem::align_of::<SlabHeader>())
+                .map_err(|_| ArenaError::LayoutOverflow)?;
 
-    let (alloc_ptr, size) = source.alloc_slab(alloc_layout)?;
-    assert!(size >= min_size);
+            let (alloc_ptr, slab_layout) = if large {
+                slabs.source.alloc_large_slab(alloc_layout)?
+            } else {
+                slabs.source.alloc_slab(alloc_layout)?
+            };
+            assert!(slab_layout.size() >= min_size && slab_layout.align() >= alloc_layout.align());
+            assert_eq!(alloc_ptr.as_ptr() as usize % slab_layout.align(), 0);
 
-    let slab = alloc_ptr.cast::<SlabHeader>();
-    let used = AtomicUsize::new(mem::size_of::<SlabHeader>());
-    ptr::write(slab.as_ptr(), SlabHeader { next, size, used });
+            let slab = alloc_ptr.cast::<SlabHeader>();
+            ptr::write(slab.as_ptr(), SlabHeader::new(next, slab_layout));
+            slab
+        }
+    };
 
     // As we just allocated our slab, we can do a non-atomic allocation.
     let ptr = alloc_in_slab_nonatomic(Some(slab), layout)
         .expect("alloc_slab produced insufficiently sized slab");
-    Some((slab, ptr))
+    if large {
+        slabs.large = Some(slab);
+        Ok((head, ptr))
+    } else {
+        Ok((Some(slab), ptr))
+    }
 }
 
 pub(crate) unsafe fn arena_drop<S: SlabSource>(
-    source: &mut S,
-    mut ptr: Option<NonNull<SlabHeader>>,
+    slabs: &mut Slabs<S>,
+    ptr: Option<NonNull<SlabHeader>>,
 ) {
+    slabs.recycle(ptr);
+    let mut ptr = slabs.spare.take();
     while let Some(curr) = ptr {
         ptr = curr.as_ref().next;
-        let layout =
-            Layout::from_size_align_unchecked(curr.as_ref().size, mem::align_of::<SlabHeader>());
-        source.dealloc_slab(curr.cast::<u8>(), layout);
+        slabs.dealloc(curr);
+    }
+}
+
+impl ArenaStats {
+    fn add_slab(&mut self, size: usize) {
+        self.slab_count += 1;
+        self.total_bytes += size;
+        self.largest_slab = cmp::max(self.largest_slab, size);
     }
 }
@@ -0,0 +1,113 @@
+use core::convert::TryInto;
+
+#[cfg(feature = "std")]
+extern crate std;
+#[cfg(feature = "std")]
+pub(crate) use std::io;
+
+const MAGIC: [u8; 8] = *b"DASNAP\0\x01";
+
+/// Each segment is placed at the same offset from a multiple of this many
+/// bytes as it had in the arena, so that data in it keeps its alignment, up to
+/// this alignment.
+const ALIGN: usize = 16;
+
+fn read_u64(bytes: &[u8], pos: usize) -> Option<usize> {
+    let field = bytes.get(pos..pos.checked_add(8)?)?;
+    u64::from_le_bytes(field.try_into().ok()?).try_into().ok()
+}
+
+fn align_up(pos: usize) -> Option<usize> {
+    Some(pos.checked_add(ALIGN - 1)? & !(ALIGN - 1))
+}
+
+/// Parse the segment starting at `pos`, returning it and the position of the
+/// next one.
+fn read_segment(bytes: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let len = read_u64(bytes, pos)?;
+    let padding = read_u64(bytes, pos + 8)?;
+    if padding >= ALIGN {
+        return None;
+    }
+    let start = pos + 16 + padding;
+    let end = start.checked_add(len)?;
+    let segment = bytes.get(start..end)?;
+    Some((segment, align_up(end)?))
+}
+
+/// Write the segments returned by `Arena::snapshot` in the format read by
+/// [`ArenaSnapshot::from_bytes`].
+#[cfg(feature = "std")]
+pub(crate) fn write_snapshot<'s, I, W>(segments: I, mut w: W) -> io::Result<()>
+where
+    I: Iterator<Item = &'s [u8]> + Clone,
+    W: io::Write,
+{
+    const ZEROS: [u8; ALIGN] = [0; ALIGN];
+
+    w.write_all(&MAGIC)?;
+    w.write_all(&(segments.clone().count() as u64).to_le_bytes())?;
+    for segment in segments {
+        // Slabs are only aligned to `SlabHeader`, so the padding needed to
+        // keep the segment's alignment depends on where it was.
+        let padding = segment.as_ptr() as usize % ALIGN;
+        w.write_all(&(segment.len() as u64).to_le_bytes())?;
+        w.write_all(&(padding as u64).to_le_bytes())?;
+        w.write_all(&ZEROS[..padding])?;
+        w.write_all(segment)?;
+        let end = padding + segment.len();
+        w.write_all(&ZEROS[..(ALIGN - end % ALIGN) % ALIGN])?;
+    }
+    Ok(())
+}
+
+/// A read-only view of a snapshot of an arena's contents, written by
+/// `Arena::write_snapshot`.
+///
+/// The snapshot is made up of segments holding the used part of each of the
+/// arena's slabs. Data in each segment keeps the alignment it had in the
+/// arena, up to 16 bytes, as long as the snapshot itself is aligned to 16
+/// bytes.
+#[derive(Copy, Clone, Debug)]
+pub struct ArenaSnapshot<'b> {
+    bytes: &'b [u8],
+    count: usize,
+}
+
+impl<'b> ArenaSnapshot<'b> {
+    /// Load a snapshot, returning `None` if `bytes` is not a valid snapshot or
+    /// is not aligned to 16 bytes.
+    pub fn from_bytes(bytes: &'b [u8]) -> Option<Self> {
+        if bytes.as_ptr().align_offset(ALIGN) != 0 || bytes.get(..8)? != MAGIC {
+            return None;
+        }
+        let count = read_u64(bytes, 8)?;
+
+        // Check every segment up front, so they can be read without checks.
+        let mut pos = 16;
+        for _ in 0..count {
+            pos = read_segment(bytes, pos)?.1;
+        }
+        if pos < bytes.len() {
+            return None;
+        }
+        Some(ArenaSnapshot { bytes, count })
+    }
+
+    /// The number of segments in the snapshot.
+    pub fn segment_count(&self) -> usize {
+        self.count
+    }
+
+    /// Iterate over the segments in the snapshot, in the order returned by
+    /// `Arena::snapshot`: the arena's slabs oldest first, followed by the
+    /// slabs holding large allocations.
+    pub fn segments(&self) -> impl Iterator<Item = &'b [u8]> + 'b {
+        let bytes = self.bytes;
+        (0..self.count).scan(16, move |pos, _| {
+            let (segment, next) = read_segment(bytes, *pos)?;
+            *pos = next;
+            Some(segment)
+        })
+    }
+}
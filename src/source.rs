@@ -1,28 +1,105 @@
+use crate::ArenaError;
 use core::alloc::Layout;
 use core::ptr::NonNull;
 
 #[cfg(any(feature = "alloc", feature = "std"))]
 mod alloc_source;
 #[cfg(any(feature = "alloc", feature = "std"))]
-pub use alloc_source::AllocSource;
+pub use alloc_source::{AllocSource, GrowthPolicy};
+
+#[cfg(any(feature = "alloc", feature = "std"))]
+mod checked_source;
+#[cfg(any(feature = "alloc", feature = "std"))]
+pub use checked_source::CheckedSource;
 
 mod buffer_source;
 pub use buffer_source::BufferSource;
 
+mod free_list;
+
+mod region_source;
+pub use region_source::RegionSource;
+
+mod stack_source;
+pub use stack_source::StackSource;
+
+#[cfg(all(feature = "mmap", target_os = "linux"))]
+mod reservation;
+
+#[cfg(all(feature = "mmap", target_os = "linux"))]
+mod mmap_source;
+#[cfg(all(feature = "mmap", target_os = "linux"))]
+pub use mmap_source::MmapSource;
+
+#[cfg(all(feature = "mmap", feature = "std", target_os = "linux"))]
+mod file_source;
+#[cfg(all(feature = "mmap", feature = "std", target_os = "linux"))]
+pub use file_source::{FileSource, MappedFile};
+
 pub unsafe trait SlabSource {
     /// Allocate a slab which must contain, at a minimum, enough space to
     /// allocate an aligned SlabHeader, followed by the object described by
     /// `Layout`, optionally with padding for alignment.
-    unsafe fn alloc_slab(&mut self, min_layout: Layout) -> Option<(NonNull<u8>, usize)>;
+    ///
+    /// Returns the slab along with its exact layout, which must be at least as
+    /// large and as aligned as `min_layout`, or the reason no slab could be
+    /// provided.
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError>;
 
-    /// Dealloc a slab which was previously allocated.
+    /// Allocate a dedicated slab for a single allocation of at least
+    /// `large_threshold` bytes, with the same requirements as `alloc_slab`.
+    ///
+    /// By default, this is the same as `alloc_slab`. Sources which scale
+    /// their slabs with usage can override it, so that large allocations
+    /// don't affect the size of later slabs.
+    unsafe fn alloc_large_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        self.alloc_slab(min_layout)
+    }
+
+    /// Dealloc a slab which was previously allocated. `layout` is the layout
+    /// which was returned from `alloc_slab` for this slab.
     unsafe fn dealloc_slab(&mut self, slab: NonNull<u8>, layout: Layout);
+
+    /// Try to extend `slab`, which currently has the given `layout`, in place
+    /// so that it is at least `min_size` bytes. Returns the new layout of the
+    /// slab, which must have the same alignment.
+    ///
+    /// By default, slabs are never grown.
+    unsafe fn grow_slab(
+        &mut self,
+        _slab: NonNull<u8>,
+        _layout: Layout,
+        _min_size: usize,
+    ) -> Option<Layout> {
+        None
+    }
+
+    /// Allocations of at least this many bytes which don't fit in the current
+    /// slab are given a dedicated slab, leaving the current slab to be used
+    /// for later allocations.
+    fn large_threshold(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// A source whose slabs are all carved out of a single region of memory, so
+/// positions in an arena can be described by their offset from its start.
+pub unsafe trait ContiguousSource: SlabSource {
+    /// The start of the region, and its current length. Every slab handed out
+    /// by the source is within it.
+    fn region(&self) -> (NonNull<u8>, usize);
 }
 
 pub unsafe trait InfallibleSource: SlabSource {
     fn handle_error(layout: Layout) -> !;
 
-    fn unwrap<T>(opt: Option<T>, layout: impl FnOnce() -> Layout) -> T {
-        opt.unwrap_or_else(|| Self::handle_error(layout()))
+    fn unwrap<T>(result: Result<T, ArenaError>, layout: impl FnOnce() -> Layout) -> T {
+        result.unwrap_or_else(|_| Self::handle_error(layout()))
     }
 }
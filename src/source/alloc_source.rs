@@ -1,41 +1,161 @@
 use crate::source::{InfallibleSource, SlabSource};
+use crate::ArenaError;
 use core::alloc::Layout;
 use core::cmp;
 use core::ptr::NonNull;
 
 extern crate alloc;
 
+/// How an [`AllocSource`] picks the size of each new slab.
+#[derive(Copy, Clone, Debug)]
+pub enum GrowthPolicy {
+    /// Every slab has the same size.
+    Fixed(usize),
+    /// The first slab has `initial` bytes, and each slab after it is twice the
+    /// size of the last, up to `max` bytes.
+    Geometric { initial: usize, max: usize },
+    /// Call a function with the index of the slab being allocated, and the
+    /// minimum layout it must fit, to get the size of the slab.
+    ///
+    /// Dedicated slabs for large allocations are sized to fit exactly, and
+    /// aren't counted by the index.
+    Custom(fn(usize, Layout) -> usize),
+}
+
+impl GrowthPolicy {
+    fn slab_size(&self, index: usize, min_layout: Layout) -> usize {
+        match *self {
+            GrowthPolicy::Fixed(size) => size,
+            GrowthPolicy::Geometric { initial, max } => {
+                let factor = 1usize.checked_shl(index as u32).unwrap_or(usize::MAX);
+                cmp::min(initial.saturating_mul(factor), max)
+            }
+            GrowthPolicy::Custom(f) => f(index, min_layout),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct AllocSource {
-    slab_size: usize,
+    policy: GrowthPolicy,
+    large_threshold: Option<usize>,
+    // The number of slabs sized by `policy` so far, which is the index of the
+    // next one. Dedicated slabs for large allocations don't advance it, so
+    // they don't inflate later slabs.
+    regular_slabs: usize,
+    // The size picked by `policy` for the most recent slab.
+    last_slab_size: usize,
+    slab_align: usize,
+    quota: Option<usize>,
+    // The total size of the slabs currently allocated from this source.
+    allocated: usize,
 }
 
 impl AllocSource {
-    pub fn new(slab_size: usize) -> AllocSource {
-        AllocSource { slab_size }
+    /// Create a source which allocates slabs of `slab_size` bytes.
+    pub const fn new(slab_size: usize) -> AllocSource {
+        AllocSource::with_policy(GrowthPolicy::Fixed(slab_size))
+    }
+
+    /// Create a source which sizes slabs using `policy`.
+    pub const fn with_policy(policy: GrowthPolicy) -> AllocSource {
+        AllocSource {
+            policy,
+            large_threshold: None,
+            regular_slabs: 0,
+            last_slab_size: 0,
+            slab_align: 1,
+            quota: None,
+            allocated: 0,
+        }
+    }
+
+    /// Align every slab to at least `slab_align` bytes, for example to a page
+    /// boundary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slab_align` is not a power of two.
+    pub fn with_slab_align(self, slab_align: usize) -> AllocSource {
+        assert!(
+            slab_align.is_power_of_two(),
+            "slab alignment must be a power of two"
+        );
+        AllocSource { slab_align, ..self }
+    }
+
+    /// Set the size at which allocations which don't fit in the current slab
+    /// are given a dedicated slab. By default, this is half the size of the
+    /// most recently allocated slab.
+    pub fn with_large_threshold(self, large_threshold: usize) -> AllocSource {
+        AllocSource {
+            large_threshold: Some(large_threshold),
+            ..self
+        }
+    }
+
+    /// Limit the total size of the slabs allocated from this source at any
+    /// one time to `quota` bytes. Allocations which would need a slab beyond
+    /// the quota fail with [`ArenaError::QuotaExceeded`].
+    pub fn with_quota(self, quota: usize) -> AllocSource {
+        AllocSource {
+            quota: Some(quota),
+            ..self
+        }
+    }
+
+    /// Allocate a slab of exactly `size` bytes, within the quota.
+    unsafe fn alloc_exact(
+        &mut self,
+        size: usize,
+        align: usize,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let align = cmp::max(align, self.slab_align);
+        let layout =
+            Layout::from_size_align(size, align).map_err(|_| ArenaError::LayoutOverflow)?;
+        if let Some(quota) = self.quota {
+            if size > quota - self.allocated {
+                return Err(ArenaError::QuotaExceeded);
+            }
+        }
+        let ptr = NonNull::new(alloc::alloc::alloc(layout)).ok_or(ArenaError::SourceFailed)?;
+        self.allocated += size;
+        Ok((ptr, layout))
     }
 }
 
 impl Default for AllocSource {
     fn default() -> Self {
-        AllocSource { slab_size: 4096 }
+        AllocSource::new(4096)
     }
 }
 
 unsafe impl SlabSource for AllocSource {
-    unsafe fn alloc_slab(&mut self, min_layout: Layout) -> Option<(NonNull<u8>, usize)> {
-        let size = cmp::max(min_layout.size(), self.slab_size);
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let slab_size = self.policy.slab_size(self.regular_slabs, min_layout);
+        let slab = self.alloc_exact(cmp::max(min_layout.size(), slab_size), min_layout.align())?;
+        self.regular_slabs += 1;
+        self.last_slab_size = slab_size;
+        Ok(slab)
+    }
 
-        // The alignment of our allocation is always based on `SlabHeader`, even
-        // if `Layout` is more-aligned. This allows the `alloc::dealloc` method
-        // to be called without storing the alignment of each slab.
-        let layout = Layout::from_size_align(size, min_layout.align()).ok()?;
-        let ptr = NonNull::new(alloc::alloc::alloc(layout))?;
-        Some((ptr, size))
+    unsafe fn alloc_large_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        self.alloc_exact(min_layout.size(), min_layout.align())
     }
 
     unsafe fn dealloc_slab(&mut self, slab: NonNull<u8>, layout: Layout) {
         alloc::alloc::dealloc(slab.as_ptr(), layout);
+        self.allocated -= layout.size();
+    }
+
+    fn large_threshold(&self) -> usize {
+        self.large_threshold.unwrap_or(self.last_slab_size / 2)
     }
 }
 
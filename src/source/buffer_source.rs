@@ -1,4 +1,5 @@
-use crate::source::SlabSource;
+use crate::source::{ContiguousSource, SlabSource};
+use crate::ArenaError;
 use core::alloc::Layout;
 use core::ptr::NonNull;
 
@@ -6,34 +7,63 @@ use core::ptr::NonNull;
 pub struct BufferSource<T> {
     buf: T,
     used: bool,
+    // The start and length of `buf`, recorded when the slab is handed out.
+    // Borrowing `buf` again while allocations in it are live would invalidate
+    // them, so `region` uses this instead.
+    region: (NonNull<u8>, usize),
 }
 
+// `region` only points into `buf`, so the source can be shared and sent like it.
+unsafe impl<T: Send> Send for BufferSource<T> {}
+unsafe impl<T: Sync> Sync for BufferSource<T> {}
+
 impl<T: AsMut<[u8]>> BufferSource<T> {
-    pub fn new(buf: T) -> Self {
-        BufferSource { buf, used: false }
+    pub const fn new(buf: T) -> Self {
+        BufferSource {
+            buf,
+            used: false,
+            region: (NonNull::dangling(), 0),
+        }
     }
 }
 
 unsafe impl<T: AsMut<[u8]>> SlabSource for BufferSource<T> {
-    unsafe fn alloc_slab(&mut self, min_layout: Layout) -> Option<(NonNull<u8>, usize)> {
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let exhausted = ArenaError::SourceExhausted {
+            requested: min_layout,
+        };
         if self.used {
-            return None;
+            return Err(exhausted);
         }
 
         let buf = self.buf.as_mut();
 
         // How much of the buffer is avaliable for use?
         let padding = buf.as_mut_ptr().align_offset(min_layout.align());
-        let size = buf.len().checked_sub(padding)?;
+        let size = buf.len().checked_sub(padding).ok_or(exhausted)?;
         if size < min_layout.size() {
-            return None;
+            return Err(exhausted);
         }
+        let layout = Layout::from_size_align(size, min_layout.align()).map_err(|_| exhausted)?;
 
         self.used = true;
-        Some((NonNull::new_unchecked(buf.as_mut_ptr().add(padding)), size))
+        self.region = (NonNull::from(&mut *buf).cast(), buf.len());
+        Ok((
+            NonNull::new_unchecked(buf.as_mut_ptr().add(padding)),
+            layout,
+        ))
     }
 
     unsafe fn dealloc_slab(&mut self, _: NonNull<u8>, _: Layout) {
         self.used = false;
     }
 }
+
+unsafe impl<T: AsMut<[u8]>> ContiguousSource for BufferSource<T> {
+    fn region(&self) -> (NonNull<u8>, usize) {
+        self.region
+    }
+}
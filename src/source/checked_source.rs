@@ -0,0 +1,145 @@
+use crate::source::{InfallibleSource, SlabSource};
+use crate::ArenaError;
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(debug_assertions)]
+use alloc::collections::BTreeMap;
+
+/// A wrapper around another [`SlabSource`] which, in debug builds, checks that
+/// every slab passed to `dealloc_slab` was allocated by `alloc_slab`, with the
+/// same layout, and (with the `std` feature) that every slab is returned before
+/// the source is dropped.
+///
+/// In release builds, this simply forwards to the wrapped source.
+#[derive(Debug, Default)]
+pub struct CheckedSource<S> {
+    source: S,
+    #[cfg(debug_assertions)]
+    live: BTreeMap<usize, Layout>,
+}
+
+impl<S> CheckedSource<S> {
+    pub fn new(source: S) -> Self {
+        CheckedSource {
+            source,
+            #[cfg(debug_assertions)]
+            live: BTreeMap::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Check a slab returned by the wrapped source for `min_layout`, and
+    /// record it as live.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn check_alloc(
+        &mut self,
+        result: Result<(NonNull<u8>, Layout), ArenaError>,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let (ptr, layout) = result?;
+
+        #[cfg(debug_assertions)]
+        {
+            assert!(
+                layout.size() >= min_layout.size() && layout.align() >= min_layout.align(),
+                "alloc_slab returned {:?}, which does not fit {:?}",
+                layout,
+                min_layout
+            );
+            assert_eq!(
+                ptr.as_ptr() as usize % layout.align(),
+                0,
+                "alloc_slab returned a misaligned slab"
+            );
+            let prev = self.live.insert(ptr.as_ptr() as usize, layout);
+            assert!(prev.is_none(), "alloc_slab returned a live slab");
+        }
+        Ok((ptr, layout))
+    }
+}
+
+unsafe impl<S: SlabSource> SlabSource for CheckedSource<S> {
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let result = self.source.alloc_slab(min_layout);
+        self.check_alloc(result, min_layout)
+    }
+
+    unsafe fn alloc_large_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let result = self.source.alloc_large_slab(min_layout);
+        self.check_alloc(result, min_layout)
+    }
+
+    unsafe fn dealloc_slab(&mut self, slab: NonNull<u8>, layout: Layout) {
+        #[cfg(debug_assertions)]
+        match self.live.remove(&(slab.as_ptr() as usize)) {
+            Some(expected) => assert_eq!(
+                layout, expected,
+                "dealloc_slab called with a different layout than alloc_slab returned"
+            ),
+            None => panic!("dealloc_slab called with a slab which is not live"),
+        }
+        self.source.dealloc_slab(slab, layout);
+    }
+
+    unsafe fn grow_slab(
+        &mut self,
+        slab: NonNull<u8>,
+        layout: Layout,
+        min_size: usize,
+    ) -> Option<Layout> {
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            self.live.get(&(slab.as_ptr() as usize)),
+            Some(&layout),
+            "grow_slab called with a different layout than alloc_slab returned"
+        );
+        let new_layout = self.source.grow_slab(slab, layout, min_size)?;
+
+        #[cfg(debug_assertions)]
+        {
+            assert!(
+                new_layout.size() >= min_size && new_layout.align() == layout.align(),
+                "grow_slab returned {:?}, which does not fit {} bytes",
+                new_layout,
+                min_size
+            );
+            self.live.insert(slab.as_ptr() as usize, new_layout);
+        }
+        Some(new_layout)
+    }
+
+    fn large_threshold(&self) -> usize {
+        self.source.large_threshold()
+    }
+}
+
+unsafe impl<S: InfallibleSource> InfallibleSource for CheckedSource<S> {
+    fn handle_error(layout: Layout) -> ! {
+        S::handle_error(layout)
+    }
+}
+
+// Leaks are only reported with `std`, which is needed to avoid panicking
+// while already unwinding.
+#[cfg(all(debug_assertions, feature = "std"))]
+impl<S> Drop for CheckedSource<S> {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            assert!(self.live.is_empty(), "slabs were leaked from CheckedSource");
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::source::reservation::Reservation;
+use crate::source::{ContiguousSource, SlabSource};
+use crate::ArenaError;
+use core::alloc::Layout;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+extern crate std;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+const MAGIC: [u8; 8] = *b"DARENA\0\x01";
+
+// Stored at the start of the file, followed by slabs starting at the next page.
+#[repr(C)]
+struct FileHeader {
+    magic: [u8; 8],
+    // Offset of the root object from the start of the file, or 0 if there is
+    // none.
+    root: u64,
+}
+
+/// A source which stores slabs in a file, so a data structure can be built in
+/// an arena once and reopened later with [`MappedFile`].
+///
+/// The file is mapped with `MAP_SHARED`, and is grown with `ftruncate` as slabs
+/// are requested. Like [`MmapSource`](super::MmapSource), address space is
+/// reserved up front, so slabs can be grown in place.
+///
+/// The file is reopened at a different address, so the data should refer to
+/// other parts of itself by offset, rather than by pointer. The location of
+/// the data is recorded with `Arena::set_root`.
+///
+/// *This type is only available on Linux, when built with the `mmap` and `std`
+/// features*
+pub struct FileSource {
+    file: File,
+    // The committed pages are the whole file, so its length is `pages.bump`.
+    pages: Reservation,
+}
+
+// The mapping is exclusively owned by the source.
+unsafe impl Send for FileSource {}
+
+impl FileSource {
+    /// Create or truncate the file at `path`, reserving `reserve` bytes of
+    /// address space for it, which is the largest the file can grow to.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        reserve: usize,
+        slab_size: usize,
+    ) -> io::Result<FileSource> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let pages = Reservation::new(reserve, slab_size).ok_or_else(io::Error::last_os_error)?;
+        let mut source = FileSource { file, pages };
+
+        // The first page holds the header.
+        unsafe {
+            let file = &source.file;
+            let commit = |addr, offset, len| map_pages(file, addr, offset, len);
+            if !source.pages.extend(source.pages.page_size, commit) {
+                return Err(io::Error::last_os_error());
+            }
+            ptr::write(
+                source.header(),
+                FileHeader {
+                    magic: MAGIC,
+                    root: 0,
+                },
+            );
+        }
+        Ok(source)
+    }
+
+    fn header(&self) -> *mut FileHeader {
+        self.pages.base.as_ptr().cast()
+    }
+
+    /// Record the location of `root`, which must have been allocated from
+    /// this source, in the file header. Used by `Arena::set_root`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `root` is not within the file.
+    pub(crate) fn set_root(&mut self, root: *const u8) {
+        let offset = (root as usize).wrapping_sub(self.pages.base.as_ptr() as usize);
+        assert!(
+            offset >= self.pages.page_size && offset < self.pages.bump,
+            "root was not allocated from this file"
+        );
+        unsafe { (*self.header()).root = offset as u64 }
+    }
+
+    /// Flush changes to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        let (base, len) = (self.pages.base.as_ptr(), self.pages.bump);
+        let res = unsafe { libc::msync(base.cast(), len, libc::MS_SYNC) };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Grow `file` to cover `len` bytes at `offset`, and map them at `addr`.
+/// Reports failures through `errno`.
+unsafe fn map_pages(file: &File, addr: NonNull<u8>, offset: usize, len: usize) -> bool {
+    if file.set_len((offset + len) as u64).is_err() {
+        return false;
+    }
+    let mapped = libc::mmap(
+        addr.as_ptr().cast(),
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_SHARED | libc::MAP_FIXED,
+        file.as_raw_fd(),
+        offset as libc::off_t,
+    );
+    mapped != libc::MAP_FAILED
+}
+
+unsafe impl SlabSource for FileSource {
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let file = &self.file;
+        let commit = |addr, offset, len| map_pages(file, addr, offset, len);
+        self.pages.alloc_slab(min_layout, commit)
+    }
+
+    unsafe fn grow_slab(
+        &mut self,
+        slab: NonNull<u8>,
+        layout: Layout,
+        min_size: usize,
+    ) -> Option<Layout> {
+        let file = &self.file;
+        let commit = |addr, offset, len| map_pages(file, addr, offset, len);
+        self.pages.grow_slab(slab, layout, min_size, commit)
+    }
+
+    unsafe fn dealloc_slab(&mut self, slab: NonNull<u8>, layout: Layout) {
+        // The contents of the file are left in place when the arena is
+        // dropped, so slabs are only kept for reuse.
+        self.pages.free.push(slab, layout);
+    }
+}
+
+// The region is the whole file, so offsets can be resolved in a `MappedFile`.
+unsafe impl ContiguousSource for FileSource {
+    fn region(&self) -> (NonNull<u8>, usize) {
+        (self.pages.base, self.pages.bump)
+    }
+}
+
+impl Drop for FileSource {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// A read-only mapping of a file written through a [`FileSource`].
+///
+/// *This type is only available on Linux, when built with the `mmap` and `std`
+/// features*
+pub struct MappedFile {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedFile> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not an arena file");
+        if len < mem::size_of::<FileHeader>() {
+            return Err(invalid());
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let mapped = MappedFile {
+            ptr: NonNull::new(ptr.cast()).ok_or(io::ErrorKind::Other)?,
+            len,
+        };
+        if mapped.header().magic != MAGIC {
+            return Err(invalid());
+        }
+        Ok(mapped)
+    }
+
+    fn header(&self) -> &FileHeader {
+        unsafe { &*self.ptr.as_ptr().cast::<FileHeader>() }
+    }
+
+    /// The contents of the file, including the header.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// The offset of the root recorded with `Arena::set_root`, from the start
+    /// of the file.
+    pub fn root_offset(&self) -> Option<usize> {
+        match self.header().root {
+            0 => None,
+            root => Some(root as usize),
+        }
+    }
+
+    /// Get a reference to the root recorded with `Arena::set_root`.
+    ///
+    /// # Safety
+    ///
+    /// The root must be a valid `T`, which doesn't contain pointers.
+    pub unsafe fn root<T>(&self) -> Option<&T> {
+        let offset = self.root_offset()?;
+        assert!(
+            offset.checked_add(mem::size_of::<T>()) <= Some(self.len),
+            "root extends past the end of the file"
+        );
+        let ptr = self.ptr.as_ptr().add(offset);
+        assert_eq!(
+            ptr.align_offset(mem::align_of::<T>()),
+            0,
+            "root is misaligned"
+        );
+        Some(&*ptr.cast::<T>())
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
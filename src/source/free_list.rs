@@ -0,0 +1,69 @@
+use core::alloc::Layout;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+// Header written into each slab on the free list.
+struct FreeSlab {
+    next: Option<NonNull<FreeSlab>>,
+    layout: Layout,
+}
+
+/// An intrusive list of slabs which have been returned to a source, for
+/// sources which carve slabs out of a single region.
+pub(crate) struct FreeList {
+    head: Option<NonNull<FreeSlab>>,
+}
+
+impl FreeList {
+    pub(crate) const fn new() -> Self {
+        FreeList { head: None }
+    }
+
+    /// Add a slab with the given layout to the list.
+    pub(crate) unsafe fn push(&mut self, slab: NonNull<u8>, layout: Layout) {
+        // Slabs always have room for a `SlabHeader`, which is at least as large
+        // and aligned as `FreeSlab`.
+        debug_assert!(layout.size() >= mem::size_of::<FreeSlab>());
+        debug_assert!(layout.align() >= mem::align_of::<FreeSlab>());
+        let free = slab.cast::<FreeSlab>();
+        ptr::write(
+            free.as_ptr(),
+            FreeSlab {
+                next: self.head,
+                layout,
+            },
+        );
+        self.head = Some(free);
+    }
+
+    /// Remove the smallest slab which fits `layout` from the list. The slab's
+    /// own layout is returned, so it must be at least as aligned as `layout`,
+    /// not just happen to be at an aligned address.
+    pub(crate) unsafe fn take(&mut self, layout: Layout) -> Option<(NonNull<u8>, Layout)> {
+        let mut best: Option<NonNull<FreeSlab>> = None;
+        let mut ptr = self.head;
+        while let Some(curr) = ptr {
+            let slab = curr.as_ref();
+            let fits = slab.layout.size() >= layout.size() && slab.layout.align() >= layout.align();
+            let better = match best {
+                Some(best) => slab.layout.size() < best.as_ref().layout.size(),
+                None => true,
+            };
+            if fits && better {
+                best = Some(curr);
+            }
+            ptr = slab.next;
+        }
+        let best = best?;
+
+        let mut link = &mut self.head;
+        while let Some(mut curr) = *link {
+            if curr == best {
+                break;
+            }
+            link = &mut curr.as_mut().next;
+        }
+        *link = best.as_ref().next;
+        Some((best.cast(), best.as_ref().layout))
+    }
+}
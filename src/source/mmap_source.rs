@@ -0,0 +1,81 @@
+use crate::source::reservation::Reservation;
+use crate::source::{ContiguousSource, SlabSource};
+use crate::ArenaError;
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+/// A source which reserves a large range of address space up front, and
+/// commits pages from it as slabs are requested.
+///
+/// Slabs are page-aligned, and are handed out in address order, so the most
+/// recent slab can usually be grown in place. This lets an arena keep bump
+/// allocating from a single contiguous slab. Returned slabs are released to
+/// the operating system with `madvise(MADV_DONTNEED)`.
+///
+/// *This type is only available on Linux, when built with the `mmap` feature*
+pub struct MmapSource {
+    pages: Reservation,
+}
+
+// The reservation is exclusively owned by the source.
+unsafe impl Send for MmapSource {}
+
+impl MmapSource {
+    /// Reserve `reserve` bytes of address space, which will be committed in
+    /// slabs of at least `slab_size` bytes. Returns `None` if the address
+    /// space could not be reserved.
+    pub fn new(reserve: usize, slab_size: usize) -> Option<MmapSource> {
+        Some(MmapSource {
+            pages: Reservation::new(reserve, slab_size)?,
+        })
+    }
+
+    /// The number of bytes which have been committed for use by slabs.
+    pub fn committed(&self) -> usize {
+        self.pages.bump
+    }
+}
+
+/// Make `len` bytes at `addr` readable and writable.
+fn commit(addr: NonNull<u8>, _offset: usize, len: usize) -> bool {
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+    unsafe { libc::mprotect(addr.as_ptr().cast(), len, prot) == 0 }
+}
+
+unsafe impl SlabSource for MmapSource {
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        self.pages.alloc_slab(min_layout, commit)
+    }
+
+    unsafe fn grow_slab(
+        &mut self,
+        slab: NonNull<u8>,
+        layout: Layout,
+        min_size: usize,
+    ) -> Option<Layout> {
+        self.pages.grow_slab(slab, layout, min_size, commit)
+    }
+
+    unsafe fn dealloc_slab(&mut self, slab: NonNull<u8>, layout: Layout) {
+        libc::madvise(slab.as_ptr().cast(), layout.size(), libc::MADV_DONTNEED);
+
+        // If this was the last slab handed out, decommit it entirely.
+        let offset = self.pages.offset_of(slab);
+        if offset + layout.size() == self.pages.bump {
+            libc::mprotect(slab.as_ptr().cast(), layout.size(), libc::PROT_NONE);
+            self.pages.bump = offset;
+            return;
+        }
+
+        self.pages.free.push(slab, layout);
+    }
+}
+
+unsafe impl ContiguousSource for MmapSource {
+    fn region(&self) -> (NonNull<u8>, usize) {
+        (self.pages.base, self.pages.bump)
+    }
+}
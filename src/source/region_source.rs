@@ -0,0 +1,114 @@
+use crate::source::free_list::FreeList;
+use crate::source::{ContiguousSource, SlabSource};
+use crate::ArenaError;
+use core::alloc::Layout;
+use core::cmp;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+/// A source which carves slabs out of a caller-provided region of memory.
+///
+/// Slabs are handed out on demand from the start of the region, and slabs
+/// which are returned are kept on a free list to be handed out again. This
+/// does not need `alloc`, so can be used in `#![no_std]` programs.
+pub struct RegionSource<'a> {
+    start: NonNull<u8>,
+    len: usize,
+    // Offset of the first byte which has never been handed out.
+    bump: usize,
+    // Bytes skipped to align the slab which ends at `bump`, if it is the most
+    // recent slab handed out. Earlier slabs' padding isn't tracked.
+    bump_padding: usize,
+    slab_size: usize,
+    free: FreeList,
+    marker: PhantomData<&'a mut [u8]>,
+}
+
+// The region is borrowed mutably, so the source can be sent like a `&mut [u8]`.
+unsafe impl<'a> Send for RegionSource<'a> {}
+
+impl<'a> RegionSource<'a> {
+    /// Create a source which hands out slabs of `slab_size` bytes from
+    /// `region`. Allocations which need more space are given a larger slab.
+    pub fn new(region: &'a mut [u8], slab_size: usize) -> Self {
+        let len = region.len();
+        RegionSource {
+            start: NonNull::from(region).cast(),
+            len,
+            bump: 0,
+            bump_padding: 0,
+            slab_size,
+            free: FreeList::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Like `new`, but for a region which hasn't been initialized.
+    pub fn from_uninit(region: &'a mut [MaybeUninit<u8>], slab_size: usize) -> Self {
+        let len = region.len();
+        RegionSource {
+            start: NonNull::from(region).cast(),
+            len,
+            bump: 0,
+            bump_padding: 0,
+            slab_size,
+            free: FreeList::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// The number of bytes which have never been handed out as part of a slab.
+    pub fn remaining(&self) -> usize {
+        self.len - self.bump
+    }
+}
+
+unsafe impl<'a> SlabSource for RegionSource<'a> {
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let size = cmp::max(min_layout.size(), self.slab_size);
+        let layout = Layout::from_size_align(size, min_layout.align())
+            .map_err(|_| ArenaError::LayoutOverflow)?;
+        if let Some(slab) = self.free.take(layout) {
+            return Ok(slab);
+        }
+
+        let bump_ptr = self.start.as_ptr().add(self.bump);
+        let padding = bump_ptr.align_offset(layout.align());
+        let end = self.bump.saturating_add(padding).saturating_add(size);
+        if end > self.len {
+            return Err(ArenaError::SourceExhausted {
+                requested: min_layout,
+            });
+        }
+        self.bump = end;
+        self.bump_padding = padding;
+        Ok((NonNull::new_unchecked(bump_ptr.add(padding)), layout))
+    }
+
+    unsafe fn dealloc_slab(&mut self, slab: NonNull<u8>, layout: Layout) {
+        // If this was the last slab handed out, return it to the unused part
+        // of the region instead, along with the padding before it.
+        let offset = slab.as_ptr() as usize - self.start.as_ptr() as usize;
+        if offset + layout.size() == self.bump {
+            self.bump = offset - self.bump_padding;
+            self.bump_padding = 0;
+            return;
+        }
+
+        self.free.push(slab, layout);
+    }
+
+    fn large_threshold(&self) -> usize {
+        self.slab_size / 2
+    }
+}
+
+unsafe impl<'a> ContiguousSource for RegionSource<'a> {
+    fn region(&self) -> (NonNull<u8>, usize) {
+        (self.start, self.len)
+    }
+}
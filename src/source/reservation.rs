@@ -0,0 +1,141 @@
+use crate::source::free_list::FreeList;
+use crate::ArenaError;
+use core::alloc::Layout;
+use core::cmp;
+use core::ptr::{self, NonNull};
+
+pub(super) fn round_up(size: usize, page_size: usize) -> Option<usize> {
+    Some(size.checked_add(page_size - 1)? & !(page_size - 1))
+}
+
+/// A range of reserved address space, whose pages are made usable in order as
+/// page-aligned slabs are requested. Shared by the sources built on `mmap`,
+/// which differ only in how pages are committed.
+///
+/// Committing is done by a callback, which is passed the address of the pages
+/// to commit, their offset from the start of the reservation, and their
+/// length, and returns `false` if they couldn't be committed.
+pub(super) struct Reservation {
+    pub(super) base: NonNull<u8>,
+    pub(super) reserved: usize,
+    // Offset of the first page which has never been committed. Everything
+    // before it is either a slab, or on the free list.
+    pub(super) bump: usize,
+    pub(super) slab_size: usize,
+    pub(super) page_size: usize,
+    pub(super) free: FreeList,
+}
+
+impl Reservation {
+    /// Reserve `reserve` bytes of address space, which will be handed out in
+    /// slabs of at least `slab_size` bytes.
+    pub(super) fn new(reserve: usize, slab_size: usize) -> Option<Reservation> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let reserved = round_up(reserve, page_size)?;
+        let base = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                reserved,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return None;
+        }
+        Some(Reservation {
+            base: NonNull::new(base.cast())?,
+            reserved,
+            bump: 0,
+            slab_size,
+            page_size,
+            free: FreeList::new(),
+        })
+    }
+
+    /// The offset of `ptr` from the start of the reservation.
+    pub(super) fn offset_of(&self, ptr: NonNull<u8>) -> usize {
+        ptr.as_ptr() as usize - self.base.as_ptr() as usize
+    }
+
+    /// Commit the next `len` bytes, which must be a multiple of the page size.
+    /// Returns `false` if they are beyond the reservation, or `commit` fails.
+    pub(super) unsafe fn extend(
+        &mut self,
+        len: usize,
+        commit: impl FnOnce(NonNull<u8>, usize, usize) -> bool,
+    ) -> bool {
+        if len > self.reserved - self.bump {
+            return false;
+        }
+        let addr = NonNull::new_unchecked(self.base.as_ptr().add(self.bump));
+        if !commit(addr, self.bump, len) {
+            return false;
+        }
+        self.bump += len;
+        true
+    }
+
+    pub(super) unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+        commit: impl FnOnce(NonNull<u8>, usize, usize) -> bool,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        if min_layout.align() > self.page_size {
+            return Err(ArenaError::SourceFailed);
+        }
+        let size = round_up(cmp::max(min_layout.size(), self.slab_size), self.page_size)
+            .ok_or(ArenaError::LayoutOverflow)?;
+        let layout = Layout::from_size_align(size, self.page_size)
+            .map_err(|_| ArenaError::LayoutOverflow)?;
+        if let Some(slab) = self.free.take(layout) {
+            return Ok(slab);
+        }
+
+        let offset = self.bump;
+        if size > self.reserved - offset {
+            return Err(ArenaError::SourceExhausted {
+                requested: min_layout,
+            });
+        }
+        if !self.extend(size, commit) {
+            return Err(ArenaError::SourceFailed);
+        }
+        Ok((
+            NonNull::new_unchecked(self.base.as_ptr().add(offset)),
+            layout,
+        ))
+    }
+
+    pub(super) unsafe fn grow_slab(
+        &mut self,
+        slab: NonNull<u8>,
+        layout: Layout,
+        min_size: usize,
+        commit: impl FnOnce(NonNull<u8>, usize, usize) -> bool,
+    ) -> Option<Layout> {
+        // Only the most recent slab is followed by uncommitted pages.
+        let offset = self.offset_of(slab);
+        if offset + layout.size() != self.bump {
+            return None;
+        }
+
+        // Grow by at least a whole slab, so growing isn't needed as often.
+        let new_size = cmp::max(min_size, layout.size().checked_add(self.slab_size)?);
+        let new_size = round_up(new_size, self.page_size)?;
+        if !self.extend(offset + new_size - self.bump, commit) {
+            return None;
+        }
+        Layout::from_size_align(new_size, layout.align()).ok()
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base.as_ptr().cast(), self.reserved);
+        }
+    }
+}
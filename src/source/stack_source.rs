@@ -0,0 +1,101 @@
+use crate::source::{ContiguousSource, SlabSource};
+use crate::ArenaError;
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Inline storage for a single slab of `N` bytes, which can be placed on the
+/// stack or in a `static`.
+///
+/// The slab source is a shared reference to the storage, `&StackSource<N>`,
+/// rather than the storage itself. An arena keeps pointers into its slabs,
+/// and arenas can be moved, for example when returned from a function, so an
+/// `Arena<StackSource<N>>` holding its storage inline would be left pointing
+/// at the storage's old location. Borrowing the storage keeps it in place for
+/// as long as the arena uses it.
+///
+/// The storage can live in a `static`, so that the arena can too:
+///
+/// ```
+/// use data_arena::Arena;
+/// use data_arena::source::StackSource;
+///
+/// static STORAGE: StackSource<4096> = StackSource::new();
+///
+/// let arena = Arena::with_source(&STORAGE);
+/// assert_eq!(arena.try_alloc(5u32), Ok(&mut 5));
+/// ```
+///
+/// Or on the stack, next to the arena:
+///
+/// ```
+/// use data_arena::Arena;
+/// use data_arena::source::StackSource;
+///
+/// let storage = StackSource::<256>::new();
+/// let arena = Arena::with_source(&storage);
+/// let values = arena.try_alloc_slice(&[1u16, 2, 3]).unwrap();
+/// assert_eq!(values, &[1, 2, 3]);
+/// ```
+///
+/// Only one arena can use the storage at a time. Other arenas will fail to
+/// allocate until it has been dropped.
+#[repr(C, align(16))]
+pub struct StackSource<const N: usize> {
+    buf: UnsafeCell<[MaybeUninit<u8>; N]>,
+    used: AtomicBool,
+}
+
+// The buffer is only accessed by the arena which claimed it through `used`.
+unsafe impl<const N: usize> Sync for StackSource<N> {}
+
+impl<const N: usize> StackSource<N> {
+    pub const fn new() -> Self {
+        StackSource {
+            buf: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            used: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<const N: usize> Default for StackSource<N> {
+    fn default() -> Self {
+        StackSource::new()
+    }
+}
+
+unsafe impl<const N: usize> SlabSource for &StackSource<N> {
+    unsafe fn alloc_slab(
+        &mut self,
+        min_layout: Layout,
+    ) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let exhausted = ArenaError::SourceExhausted {
+            requested: min_layout,
+        };
+        let buf = self.buf.get().cast::<u8>();
+        let padding = buf.align_offset(min_layout.align());
+        let size = N.checked_sub(padding).ok_or(exhausted)?;
+        if size < min_layout.size() {
+            return Err(exhausted);
+        }
+        let layout = Layout::from_size_align(size, min_layout.align()).map_err(|_| exhausted)?;
+
+        if self.used.swap(true, Ordering::Acquire) {
+            return Err(exhausted);
+        }
+        Ok((NonNull::new_unchecked(buf.add(padding)), layout))
+    }
+
+    unsafe fn dealloc_slab(&mut self, _: NonNull<u8>, _: Layout) {
+        self.used.store(false, Ordering::Release);
+    }
+}
+
+unsafe impl<const N: usize> ContiguousSource for &StackSource<N> {
+    fn region(&self) -> (NonNull<u8>, usize) {
+        let buf = unsafe { NonNull::new_unchecked(self.buf.get().cast::<u8>()) };
+        (buf, N)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::source::InfallibleSource;
+use crate::{ArenaError, ArenaVec, RawArena};
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str;
+
+/// A growable string whose buffer is allocated in an arena.
+///
+/// Implements `fmt::Write`, so can be written to with `write!`. See also
+/// [`arena_format!`](crate::arena_format).
+pub struct ArenaString<'arena, A: RawArena + ?Sized> {
+    vec: ArenaVec<'arena, u8, A>,
+}
+
+impl<'arena, A: RawArena + ?Sized> ArenaString<'arena, A> {
+    pub fn new_in(arena: &'arena A) -> Self {
+        ArenaString {
+            vec: ArenaVec::new_in(arena),
+        }
+    }
+
+    pub fn try_with_capacity_in(capacity: usize, arena: &'arena A) -> Result<Self, ArenaError> {
+        Ok(ArenaString {
+            vec: ArenaVec::try_with_capacity_in(capacity, arena)?,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.vec.capacity()
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(&self.vec) }
+    }
+
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { str::from_utf8_unchecked_mut(&mut self.vec) }
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), ArenaError> {
+        self.vec.try_reserve(additional)
+    }
+
+    pub fn try_push_str(&mut self, s: &str) -> Result<(), ArenaError> {
+        self.vec.try_extend_from_slice(s.as_bytes())
+    }
+
+    pub fn try_push(&mut self, c: char) -> Result<(), ArenaError> {
+        self.try_push_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Shorten the string to `len` bytes. Has no effect if the string is
+    /// already shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is not on a char boundary.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            assert!(self.as_str().is_char_boundary(len));
+            self.vec.truncate(len);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.vec.clear();
+    }
+
+    /// Convert the string into a `str` which lives as long as the arena.
+    /// Unused capacity is returned to the arena if possible.
+    pub fn into_str(self) -> &'arena mut str {
+        unsafe { str::from_utf8_unchecked_mut(self.vec.into_slice()) }
+    }
+}
+
+impl<'arena, A> ArenaString<'arena, A>
+where
+    A: RawArena + ?Sized,
+    A::Source: InfallibleSource,
+{
+    pub fn with_capacity_in(capacity: usize, arena: &'arena A) -> Self {
+        ArenaString {
+            vec: ArenaVec::with_capacity_in(capacity, arena),
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.vec.reserve(additional);
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.vec.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+}
+
+impl<'arena, A: RawArena + ?Sized> fmt::Write for ArenaString<'arena, A> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl<'arena, A: RawArena + ?Sized> Deref for ArenaString<'arena, A> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<'arena, A: RawArena + ?Sized> DerefMut for ArenaString<'arena, A> {
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl<'arena, A: RawArena + ?Sized> fmt::Debug for ArenaString<'arena, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<'arena, A: RawArena + ?Sized> fmt::Display for ArenaString<'arena, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
@@ -0,0 +1,67 @@
+//! The atomics used by `SyncArena`, which are replaced by loom's when built
+//! with `--cfg loom`, so that its orderings can be model checked.
+
+#[cfg(not(loom))]
+pub(crate) use core::hint::spin_loop;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+#[cfg(loom)]
+pub(crate) use loom::hint::spin_loop;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/// Declare a function which is `const`, except under loom, whose atomics
+/// can't be created in a constant context.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])*
+        $vis const fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$attr])*
+        $vis fn $($rest)*
+    };
+}
+
+/// Access to an atomic through a unique reference, standing in for
+/// `get_mut`, which loom doesn't provide.
+pub(crate) trait AtomicMut {
+    type Value;
+
+    fn load_mut(&mut self) -> Self::Value;
+
+    fn store_mut(&mut self, value: Self::Value);
+}
+
+macro_rules! atomic_mut {
+    ([$($T:ident)?] $Atomic:ty, $Value:ty) => {
+        impl<$($T)?> AtomicMut for $Atomic {
+            type Value = $Value;
+
+            #[cfg(not(loom))]
+            fn load_mut(&mut self) -> $Value {
+                *self.get_mut()
+            }
+
+            #[cfg(not(loom))]
+            fn store_mut(&mut self, value: $Value) {
+                *self.get_mut() = value;
+            }
+
+            #[cfg(loom)]
+            fn load_mut(&mut self) -> $Value {
+                self.with_mut(|v| *v)
+            }
+
+            #[cfg(loom)]
+            fn store_mut(&mut self, value: $Value) {
+                self.with_mut(|v| *v = value)
+            }
+        }
+    };
+}
+
+atomic_mut!([] AtomicUsize, usize);
+atomic_mut!([T] AtomicPtr<T>, *mut T);
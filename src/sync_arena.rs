@@ -1,37 +1,74 @@
-use crate::slab::{alloc_in_slab_atomic, alloc_slow, arena_drop, SlabHeader};
+use crate::drops::{run_drops, DropRecord};
+use crate::lock::{DefaultLock, Lock, LockGuard, RawLock};
+use crate::slab::{
+    alloc_in_slab_atomic, alloc_slow, arena_drop, resize_in_slab_atomic, RetiredSlabs, SlabHeader,
+    Slabs,
+};
 use crate::source::SlabSource;
-
-extern crate std;
-use std::sync::{LockResult, Mutex};
+use crate::sync::{AtomicMut, AtomicPtr, Ordering};
+use crate::ArenaError;
 
 use core::alloc::Layout;
 use core::marker::PhantomData;
+use core::mem;
 use core::ptr::{self, NonNull};
-use core::sync::atomic::{AtomicPtr, Ordering};
-
-fn ignore_poison<T>(result: LockResult<T>) -> T {
-    match result {
-        Ok(v) => v,
-        Err(e) => e.into_inner(),
-    }
-}
 
 /// A threadsafe untyped lifecycle-managing arena.
 ///
-/// *This type is only available when built with the `std` feature*
-pub struct SyncArena<'a, S: SlabSource> {
+/// Allocation is lock-free, apart from calls into the `SlabSource`, which are
+/// serialized by a lock of type `L`. See the [`lock`](crate::lock) module for
+/// the available locks.
+pub struct SyncArena<'a, S: SlabSource, L: RawLock = DefaultLock> {
     slab: AtomicPtr<SlabHeader>,
-    source: Mutex<S>,
-    marker: PhantomData<&'a ()>,
+    slabs: Lock<L, Slabs<S>>,
+    drops: AtomicPtr<DropRecord>,
+    // Invariant, as destructors registered by `alloc_drop` may borrow for 'a.
+    marker: PhantomData<fn(&'a ()) -> &'a ()>,
 }
 
-arena_common!(SyncArena);
+arena_common!(SyncArena<L: RawLock>, Send);
 
 impl<'a, S: SlabSource> SyncArena<'a, S> {
-    pub unsafe fn try_alloc_raw(&self, layout: Layout) -> Option<NonNull<u8>> {
-        let slab = NonNull::new(self.slab.load(Ordering::Relaxed));
+    const_fn! {
+        pub fn with_source(source: S) -> Self {
+            Self::with_source_and_lock(source, DefaultLock::new())
+        }
+    }
+
+    /// Rebuild an arena from the parts of another arena.
+    pub(crate) unsafe fn from_parts(
+        slab: Option<NonNull<SlabHeader>>,
+        slabs: Slabs<S>,
+        drops: Option<NonNull<DropRecord>>,
+    ) -> Self {
+        SyncArena {
+            slab: AtomicPtr::new(slab.map_or(ptr::null_mut(), NonNull::as_ptr)),
+            slabs: Lock::new(DefaultLock::new(), slabs),
+            drops: AtomicPtr::new(drops.map_or(ptr::null_mut(), NonNull::as_ptr)),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, S: SlabSource, L: RawLock> SyncArena<'a, S, L> {
+    const_fn! {
+        /// Create an arena which serializes calls into `source` with `lock`.
+        pub fn with_source_and_lock(source: S, lock: L) -> Self {
+            SyncArena {
+                slab: AtomicPtr::new(ptr::null_mut()),
+                slabs: Lock::new(lock, Slabs::new(source)),
+                drops: AtomicPtr::new(ptr::null_mut()),
+                marker: PhantomData,
+            }
+        }
+    }
+
+    pub unsafe fn try_alloc_raw(&self, layout: Layout) -> Result<NonNull<u8>, ArenaError> {
+        // Pairs with the `Release` ordering used to install new slabs, so that
+        // their headers are visible.
+        let slab = NonNull::new(self.slab.load(Ordering::Acquire));
         if let Some(ptr) = alloc_in_slab_atomic(slab, layout) {
-            return Some(ptr);
+            return Ok(ptr);
         }
 
         self.try_alloc_raw_slow(layout, slab)
@@ -41,49 +78,163 @@ impl<'a, S: SlabSource> SyncArena<'a, S> {
     unsafe fn try_alloc_raw_slow(
         &self,
         layout: Layout,
-        orig_slab: Option<NonNull<SlabHeader>>,
-    ) -> Option<NonNull<u8>> {
-        // Acquire the slab source lock. After this has been acquired, the
-        // `slab` member cannot be changed by another thread.
-        let mut source_guard = ignore_poison(self.source.lock());
-
-        // Check if the slab value has changed since the last time it was read.
-        // If it has, try to allocate in the new slab.
-        //
-        // XXX: This uses `Acquire` ordering to match with the release ordering
-        // used to update the `slab` ptr at the end of this method. I am unsure
-        // whether `Mutex` lock/unlock is sufficient to provide the ordering I
-        // need here.
-        let old_slab = NonNull::new(self.slab.load(Ordering::Acquire));
-        if old_slab != orig_slab {
-            if let Some(ptr) = alloc_in_slab_atomic(old_slab, layout) {
-                return Some(ptr);
+        mut head: Option<NonNull<SlabHeader>>,
+    ) -> Result<NonNull<u8>, ArenaError> {
+        loop {
+            let (slab, ptr) = {
+                let mut slabs_guard = self.slabs.lock();
+
+                // Another thread may have installed a new slab while the lock
+                // was being acquired. If so, try to allocate in it first.
+                let current = NonNull::new(self.slab.load(Ordering::Acquire));
+                if current != head {
+                    head = current;
+                    if let Some(ptr) = alloc_in_slab_atomic(head, layout) {
+                        return Ok(ptr);
+                    }
+                }
+
+                alloc_slow(&mut *slabs_guard, layout, head)?
+            };
+
+            // The allocation was made in `head` after growing it, or in a
+            // dedicated slab for large allocations.
+            if slab == head {
+                return Ok(ptr);
+            }
+
+            // Otherwise, publish the new slab, which was linked in front of
+            // `head`. New slabs are installed without holding the lock, so
+            // another thread may have got there first.
+            let new = slab.map_or(ptr::null_mut(), NonNull::as_ptr);
+            let old = head.map_or(ptr::null_mut(), NonNull::as_ptr);
+            match self
+                .slab
+                .compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return Ok(ptr),
+                Err(current) => {
+                    // Our slab lost, so it is kept as a spare, and the
+                    // allocation is retried in the winning slab.
+                    if let Some(slab) = slab {
+                        self.slabs.lock().recycle_unused(slab);
+                    }
+                    head = NonNull::new(current);
+                    if let Some(ptr) = alloc_in_slab_atomic(head, layout) {
+                        return Ok(ptr);
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) unsafe fn resize_in_place(&self, ptr: NonNull<u8>, old_size: usize, new_size: usize) -> bool {
+        // Pairs with the `Release` ordering used to install new slabs, as the
+        // header of the slab is read, even when `ptr` isn't in it.
+        let slab = NonNull::new(self.slab.load(Ordering::Acquire));
+        resize_in_slab_atomic(slab, ptr, old_size, new_size)
+    }
+
+    /// The default chunk size for a `LocalHandle`, and the slab it was
+    /// computed for.
+    pub(crate) fn local_chunk_size(&self) -> (*mut SlabHeader, usize) {
+        self.with_slabs(|slabs, head| {
+            let size = unsafe { slabs.local_chunk_size(head) };
+            (head.map_or(ptr::null_mut(), NonNull::as_ptr), size)
+        })
+    }
+
+    /// The slab currently being allocated from. This is only used to tell
+    /// whether it has changed, so isn't dereferenced.
+    pub(crate) fn current_slab_ptr(&self) -> *mut SlabHeader {
+        self.slab.load(Ordering::Relaxed)
+    }
+
+    /// Convert this into an [`Arena`](crate::Arena), keeping every existing
+    /// allocation.
+    pub fn into_local(self) -> crate::Arena<'a, S> {
+        let mut this = mem::ManuallyDrop::new(self);
+        let slab = NonNull::new(this.slab.load_mut());
+        let drops = NonNull::new(this.drops.load_mut());
+        let slabs = unsafe { ptr::read(&this.slabs) }.into_inner();
+        unsafe { crate::Arena::from_parts(slab, slabs, drops) }
+    }
+
+    /// Take the lock around the arena's slabs.
+    pub(crate) fn lock_slabs(&self) -> LockGuard<'_, L, Slabs<S>> {
+        self.slabs.lock()
+    }
+
+    /// Detach the arena's slab lists, so that later allocations start new
+    /// ones. `slabs` must be this arena's, locked with `lock_slabs`.
+    pub(crate) fn retire_slabs(&self, slabs: &mut Slabs<S>) -> RetiredSlabs {
+        let head = self.slab.swap(ptr::null_mut(), Ordering::AcqRel);
+        slabs.retire(NonNull::new(head))
+    }
+
+    fn with_slabs<R>(&self, f: impl FnOnce(&mut Slabs<S>, Option<NonNull<SlabHeader>>) -> R) -> R {
+        // New slabs may be installed while `f` runs, but they are only ever
+        // linked in front of the current head.
+        let mut slabs_guard = self.slabs.lock();
+        f(&mut *slabs_guard, NonNull::new(self.slab.load(Ordering::Acquire)))
+    }
+
+    fn replace_slab(&mut self, slab: Option<NonNull<SlabHeader>>) -> Option<NonNull<SlabHeader>> {
+        let new = slab.map_or(ptr::null_mut(), NonNull::as_ptr);
+        let old = self.slab.load_mut();
+        self.slab.store_mut(new);
+        NonNull::new(old)
+    }
+
+    fn slabs_mut(&mut self) -> &mut Slabs<S> {
+        self.slabs.get_mut()
+    }
+
+    unsafe fn push_drop(&self, record: NonNull<DropRecord>) {
+        // The record must be fully written before it is published, so this
+        // uses `Release` ordering. Records are only read with `&mut self`.
+        let mut prev = self.drops.load(Ordering::Relaxed);
+        loop {
+            DropRecord::set_prev(record, NonNull::new(prev));
+            match self.drops.compare_exchange_weak(
+                prev,
+                record.as_ptr(),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(next_prev) => prev = next_prev,
             }
         }
+    }
 
-        // A new allocation is needed. Perform the allocation and add it to the
-        // front of the list.
-        let (slab, ptr) = alloc_slow(&mut *source_guard, layout, old_slab)?;
+    fn current_drops(&self) -> Option<NonNull<DropRecord>> {
+        NonNull::new(self.drops.load(Ordering::Acquire))
+    }
+
+    fn take_drops(&mut self) -> Option<NonNull<DropRecord>> {
+        let drops = self.drops.load_mut();
+        self.drops.store_mut(ptr::null_mut());
+        NonNull::new(drops)
+    }
 
-        // This store is OK, as no thread will write to `self.slab` without
-        // holding the alloc lock.
-        //
-        // XXX: A CAS-loop could be used instead of the `alloc_guard` if
-        // `no_std` support is desired.
-        self.slab.store(slab.as_ptr(), Ordering::Release);
-        Some(ptr)
+    fn replace_drops(&mut self, drops: Option<NonNull<DropRecord>>) {
+        self.drops
+            .store_mut(drops.map_or(ptr::null_mut(), NonNull::as_ptr));
     }
 }
 
-impl<'a, S: SlabSource> Drop for SyncArena<'a, S> {
+impl<'a, S: SlabSource, L: RawLock> Drop for SyncArena<'a, S, L> {
     fn drop(&mut self) {
-        // XXX: Not sure if I need to fence here to make sure this thread has
-        // seem atomic loads/stores from other threads?
+        // No fence is needed here. Whatever gave this thread `&mut self`, such
+        // as joining the other threads using the arena, already made their
+        // writes visible. The `drop_after_threads` loom model checks this for
+        // the arena's atomics, though not for the drop records themselves,
+        // which loom can't see.
         unsafe {
-            arena_drop(
-                ignore_poison(self.source.get_mut()),
-                NonNull::new(*self.slab.get_mut()),
-            );
+            run_drops(self.take_drops(), None);
+            let slab = NonNull::new(self.slab.load_mut());
+            arena_drop(self.slabs.get_mut(), slab);
         }
     }
 }
@@ -1,10 +1,16 @@
 extern crate std;
 
-use super::Arena;
-use super::source::{AllocSource, SlabSource, InfallibleSource};
+use super::{Arena, ArenaError, ArenaStats, ArenaString, ArenaVec, Interner, SyncArena, SyncInterner};
+use super::source::{
+    AllocSource, BufferSource, CheckedSource, ContiguousSource, GrowthPolicy, InfallibleSource,
+    RegionSource, SlabSource, StackSource,
+};
 use std::mem;
 use std::alloc::Layout;
 use std::cell::RefCell;
+use std::rc::Rc;
+use std::string::{String, ToString};
+use std::sync::Arc;
 use std::vec::Vec;
 use std::ptr::NonNull;
 
@@ -96,7 +102,7 @@ fn slice() {
     assert_eq!(s1_p - s0_p, 16);
 }
 
-const HEADER_SIZE: usize = mem::size_of::<usize>() * 3;
+const HEADER_SIZE: usize = mem::size_of::<crate::slab::SlabHeader>();
 
 struct TraceSource<'a> {
     source: AllocSource,
@@ -108,22 +114,36 @@ impl<'a> TraceSource<'a> {
         let source = AllocSource::new(HEADER_SIZE + size);
         TraceSource {source, record }
     }
+
+    fn with_source(source: AllocSource, record: &'a RefCell<Vec<(NonNull<u8>, usize)>>) -> Self {
+        TraceSource { source, record }
+    }
 }
 
 unsafe impl<'a> SlabSource for TraceSource<'a> {
-    unsafe fn alloc_slab(&mut self, layout: Layout) -> Option<(NonNull<u8>, usize)> {
-        let v = self.source.alloc_slab(layout)?;
-        self.record.borrow_mut().push(v);
-        Some(v)
+    unsafe fn alloc_slab(&mut self, layout: Layout) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let (ptr, layout) = self.source.alloc_slab(layout)?;
+        self.record.borrow_mut().push((ptr, layout.size()));
+        Ok((ptr, layout))
+    }
+
+    unsafe fn alloc_large_slab(&mut self, layout: Layout) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        let (ptr, layout) = self.source.alloc_large_slab(layout)?;
+        self.record.borrow_mut().push((ptr, layout.size()));
+        Ok((ptr, layout))
+    }
+
+    fn large_threshold(&self) -> usize {
+        self.source.large_threshold()
     }
 
     unsafe fn dealloc_slab(&mut self, ptr: NonNull<u8>, layout: Layout) {
         self.record.borrow_mut().retain(|&(old_ptr, size)| {
             if old_ptr == ptr {
                 assert_eq!(size, layout.size());
-                true
-            } else {
                 false
+            } else {
+                true
             }
         });
         self.source.dealloc_slab(ptr, layout);
@@ -143,6 +163,31 @@ impl<'a> Drop for TraceSource<'a> {
     }
 }
 
+/// Wraps a fallible source, panicking if it fails to allocate.
+struct PanicSource<S>(S);
+
+unsafe impl<S: SlabSource> SlabSource for PanicSource<S> {
+    unsafe fn alloc_slab(&mut self, layout: Layout) -> Result<(NonNull<u8>, Layout), ArenaError> {
+        self.0.alloc_slab(layout)
+    }
+
+    unsafe fn dealloc_slab(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.dealloc_slab(ptr, layout)
+    }
+}
+
+unsafe impl<S: ContiguousSource> ContiguousSource for PanicSource<S> {
+    fn region(&self) -> (NonNull<u8>, usize) {
+        self.0.region()
+    }
+}
+
+unsafe impl<S: SlabSource> InfallibleSource for PanicSource<S> {
+    fn handle_error(layout: Layout) -> ! {
+        panic!("arena alloc error: {:?}", layout);
+    }
+}
+
 #[test]
 fn full() {
     let record = RefCell::new(Vec::new());
@@ -195,8 +240,9 @@ fn large() {
     let t2 = arena.alloc_slice(&[90u8; 512][..]);
     assert_eq!(record.borrow().len(), 2);
 
+    // The large allocation got its own slab, so the first slab is still used.
     let t3 = arena.alloc(30u32);
-    assert_eq!(record.borrow().len(), 3);
+    assert_eq!(record.borrow().len(), 2);
 
     assert_eq!(t1, &10);
     assert_eq!(t2, &[90u8; 512][..]);
@@ -207,8 +253,1128 @@ fn large() {
     let t3_p = check_ptr(t3);
 
     assert_ne!(t1_p + 4, t2_p);
+    assert_eq!(t1_p + 4, t3_p);
+}
+
+#[test]
+fn large_threshold() {
+    let record = RefCell::new(Vec::new());
+    let source = AllocSource::new(HEADER_SIZE + 16).with_large_threshold(8);
+    let arena = Arena::with_source(TraceSource::with_source(source, &record));
+
+    arena.alloc(1u64);
+    let t2 = check_ptr(arena.alloc(2u32));
+    arena.alloc_slice(&[3u32; 2]);
+    let t4 = check_ptr(arena.alloc(4u32));
+    assert_eq!(record.borrow().len(), 2);
+    assert_eq!(t2 + 4, t4);
+
+    // The large slab is released by rewinding.
+    let mut arena = arena;
+    let checkpoint = arena.checkpoint();
+    arena.alloc_slice(&[5u32; 8]);
+    arena.alloc_slice(&[6u32; 8]);
+    assert_eq!(arena.stats().slab_count, 4);
+    arena.rewind(checkpoint);
+    assert_eq!(arena.stats().spare_bytes, 2 * (HEADER_SIZE + 32));
+}
+
+#[test]
+fn reset() {
+    let record = RefCell::new(Vec::new());
+    let mut arena = Arena::with_source(TraceSource::new(16, &record));
+
+    let mut first = Vec::new();
+    for i in 0..8u32 {
+        first.push(check_ptr(arena.alloc(i)));
+    }
+    arena.alloc_slice(&[90u8; 512][..]);
+    assert_eq!(record.borrow().len(), 3);
+    let slabs = record.borrow().clone();
+
+    // After a reset, the same allocations should be served from the same slabs
+    // without asking the source for more memory.
+    arena.reset();
+    for (i, &p) in first.iter().enumerate() {
+        let t = arena.alloc(i as u32 * 10);
+        assert_eq!(*t, i as u32 * 10);
+        assert_eq!(check_ptr(t), p);
+    }
+    arena.alloc_slice(&[90u8; 512][..]);
+    assert_eq!(*record.borrow(), slabs);
+
+    // Shrinking keeps only the oversized slab.
+    arena.reset_and_shrink();
+    assert_eq!(record.borrow().len(), 1);
+    arena.alloc_slice(&[90u8; 512][..]);
+    assert_eq!(record.borrow().len(), 1);
+}
+
+#[test]
+fn checkpoint() {
+    let record = RefCell::new(Vec::new());
+    let mut arena = Arena::with_source(TraceSource::new(16, &record));
+
+    let before = check_ptr(arena.alloc(10u32));
+    let checkpoint = arena.checkpoint();
+    let scratch = check_ptr(arena.alloc(20u32));
+    for i in 0..8u32 {
+        arena.alloc(i);
+    }
+    assert_eq!(record.borrow().len(), 3);
+
+    // Rewinding frees the scratch allocations, but keeps the slabs.
+    arena.rewind(checkpoint);
+    assert_eq!(record.borrow().len(), 3);
+    assert_eq!(check_ptr(arena.alloc(30u32)), scratch);
+    assert_eq!(before + 4, scratch);
+
+    // Slabs freed by the rewind are reused before asking the source.
+    for i in 0..8u32 {
+        arena.alloc(i);
+    }
+    assert_eq!(record.borrow().len(), 3);
+}
+
+#[test]
+fn scope() {
+    let mut arena = Arena::new();
+    let outer = check_ptr(arena.alloc(10u32));
+
+    let inner = arena.scope(|sub| {
+        let v = sub.alloc_slice(&[1u32, 2, 3]);
+        assert_eq!(v, &[1, 2, 3]);
+        check_slice(v)
+    });
+    assert_eq!(outer + 4, inner);
+
+    // The scope's allocations were released on exit.
+    assert_eq!(check_ptr(arena.alloc(20u32)), inner);
+}
+
+struct PushOnDrop<'a>(u32, &'a RefCell<Vec<u32>>);
+
+impl<'a> Drop for PushOnDrop<'a> {
+    fn drop(&mut self) {
+        self.1.borrow_mut().push(self.0);
+    }
+}
+
+#[test]
+fn drop_order() {
+    let dropped = RefCell::new(Vec::new());
+    {
+        let arena = Arena::new();
+        arena.alloc_drop(PushOnDrop(1, &dropped));
+        arena.alloc_from_iter_drop((2..5).map(|i| PushOnDrop(i, &dropped)), 3);
+        arena.alloc_drop(PushOnDrop(5, &dropped));
+        assert!(dropped.borrow().is_empty());
+    }
+    assert_eq!(*dropped.borrow(), [5, 2, 3, 4, 1]);
+}
+
+#[test]
+fn drop_non_copy() {
+    let rc = Rc::new(());
+    {
+        let arena = Arena::new();
+        let s = arena.alloc_drop("hello".to_string());
+        s.push_str(" world");
+        let v = arena.alloc_drop(std::vec![Rc::clone(&rc); 4]);
+        assert_eq!(Rc::strong_count(&rc), 5);
+        assert_eq!(s, "hello world");
+        assert_eq!(v.len(), 4);
+    }
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
+
+#[test]
+fn drop_rewind() {
+    let dropped = RefCell::new(Vec::new());
+    let mut arena = Arena::new();
+    arena.alloc_drop(PushOnDrop(1, &dropped));
+    arena.scope(|sub| {
+        sub.alloc_drop(PushOnDrop(2, &dropped));
+        sub.alloc_drop(PushOnDrop(3, &dropped));
+    });
+    assert_eq!(*dropped.borrow(), [3, 2]);
+
+    arena.alloc_drop(PushOnDrop(4, &dropped));
+    arena.reset();
+    assert_eq!(*dropped.borrow(), [3, 2, 4, 1]);
+}
+
+#[test]
+fn drop_failed_alloc() {
+    let mut buf = [0xffu8; 512];
+    let mut arena = Arena::with_source(BufferSource::new(&mut buf[..]));
+    arena.try_alloc(1u32).unwrap();
+    let used = arena.stats().allocated_bytes;
+
+    // The value doesn't fit, so the record reserved for its destructor is
+    // given back.
+    let strings = [(); 64].map(|_| String::new());
+    assert!(arena.try_alloc_drop(strings).is_err());
+    assert_eq!(arena.stats().allocated_bytes, used);
+
+    // Slots a short iterator doesn't fill are zeroed.
+    assert_eq!(arena.try_alloc_from_iter(1..3u32, 4).unwrap(), [1, 2]);
+    let bytes: Vec<u8> = unsafe { arena.snapshot() }.flatten().copied().collect();
+    assert_eq!(bytes[bytes.len() - 8..], [0; 8]);
+}
+
+#[test]
+fn sync_drop() {
+    let counter = Arc::new(());
+    {
+        let arena = SyncArena::new();
+        std::thread::scope(|s| {
+            for i in 0..4 {
+                let arena = &arena;
+                let counter = &counter;
+                s.spawn(move || {
+                    for j in 0..16 {
+                        arena.alloc_drop(Arc::clone(counter));
+                        arena.alloc_drop(String::from("x")).push_str(&(i * j).to_string());
+                    }
+                });
+            }
+        });
+        assert_eq!(Arc::strong_count(&counter), 65);
+    }
+    assert_eq!(Arc::strong_count(&counter), 1);
+}
+
+#[cfg(feature = "allocator-api2")]
+#[test]
+fn allocator_vec() {
+    use allocator_api2::vec::Vec;
+
+    let arena = Arena::new();
+    let mut v = Vec::with_capacity_in(4, &arena);
+    v.extend_from_slice(&[1u32, 2, 3, 4]);
+    let first = check_slice(&v);
+
+    // As the vector is the most recent allocation, it grows in place.
+    v.extend_from_slice(&[5, 6, 7, 8]);
+    assert_eq!(check_slice(&v), first);
+    assert_eq!(v, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+    // Once something else has been allocated, it must move.
+    let other = arena.alloc(9u32);
+    v.reserve(64);
+    assert_ne!(check_slice(&v), first);
+    assert_eq!(v, [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(*other, 9);
+
+    // Deallocating the most recent allocation returns its space.
+    let last = check_slice(&v);
+    drop(v);
+    assert_eq!(check_ptr(arena.alloc(10u32)), last);
+}
+
+#[cfg(feature = "allocator-api2")]
+#[test]
+fn allocator_sync() {
+    use allocator_api2::vec::Vec;
+
+    let arena = SyncArena::new();
+    let mut v = Vec::new_in(&arena);
+    for i in 0..1000u32 {
+        v.push(i);
+    }
+    assert_eq!(v.iter().sum::<u32>(), 499500);
+}
+
+#[cfg(feature = "allocator_api")]
+#[test]
+fn allocator_api_vec() {
+    let arena = Arena::new();
+    let mut v = Vec::with_capacity_in(2, &arena);
+    v.extend_from_slice(&[1u32, 2, 3, 4]);
+    assert_eq!(v, [1, 2, 3, 4]);
+}
+
+#[test]
+fn realloc() {
+    let arena = Arena::new();
+    unsafe {
+        let small = Layout::new::<[u32; 2]>();
+        let big = Layout::new::<[u32; 8]>();
+
+        let a = arena.alloc_raw(small);
+        a.cast::<[u32; 2]>().as_ptr().write([1, 2]);
+
+        // Growing and shrinking the most recent allocation happens in place.
+        let b = arena.realloc_raw(a, small, big);
+        assert_eq!(a, b);
+        let c = arena.realloc_raw(b, big, small);
+        assert_eq!(a, c);
+        let next = arena.alloc(0u32) as *mut u32 as *mut u8;
+        assert_eq!(next, a.as_ptr().add(small.size()));
+
+        // Otherwise, the contents are moved to a new allocation.
+        let d = arena.realloc_raw(c, small, big);
+        assert_ne!(c, d);
+        assert_eq!(*d.cast::<[u32; 2]>().as_ptr(), [1, 2]);
+    }
+}
+
+#[test]
+fn sync_realloc() {
+    let arena = SyncArena::new();
+    unsafe {
+        let small = Layout::new::<[u32; 2]>();
+        let big = Layout::new::<[u32; 8]>();
+
+        let a = arena.alloc_raw(small);
+        let b = arena.realloc_raw(a, small, big);
+        assert_eq!(a, b);
+
+        // Another allocation bumped the slab in between, so this cannot be
+        // done in place.
+        arena.alloc(0u32);
+        let c = arena.realloc_raw(b, big, Layout::new::<[u32; 16]>());
+        assert_ne!(b, c);
+    }
+}
+
+#[test]
+fn vec() {
+    let arena = Arena::new();
+    let mut v = ArenaVec::new_in(&arena);
+    v.push(1u32);
+    v.extend(vec_items());
+    let first = check_slice(&v);
+    v.reserve(100);
+    assert_eq!(check_slice(&v), first, "should grow in place");
+
+    v.insert(0, 0);
+    v.insert(3, 99);
+    assert_eq!(*v, [0, 1, 2, 99, 3, 4]);
+    v.truncate(3);
+    assert_eq!(v.pop(), Some(2));
+
+    // The unused capacity is released when converting into a slice.
+    let s = v.into_slice();
+    assert_eq!(s, &[0, 1]);
+    assert_eq!(check_ptr(arena.alloc(5u32)), check_slice(s) + 8);
+}
+
+fn vec_items() -> impl Iterator<Item = u32> {
+    2..5
+}
+
+#[test]
+fn vec_drop() {
+    let dropped = RefCell::new(Vec::new());
+    let arena = Arena::new();
+    let mut v = ArenaVec::new_in(&arena);
+    for i in 0..4 {
+        v.push(PushOnDrop(i, &dropped));
+    }
+    v.truncate(2);
+    assert_eq!(*dropped.borrow(), [2, 3]);
+    drop(v);
+    assert_eq!(*dropped.borrow(), [2, 3, 0, 1]);
+}
+
+#[test]
+fn vec_sync_and_buffer() {
+    let arena = SyncArena::new();
+    let mut v = ArenaVec::with_capacity_in(2, &arena);
+    v.extend(0..100u64);
+    assert_eq!(v.iter().sum::<u64>(), 4950);
+
+    let mut buf = [0u8; 256];
+    let arena = Arena::with_source(BufferSource::new(&mut buf[..]));
+    let mut v = ArenaVec::new_in(&arena);
+    let mut pushed = 0u32;
+    while v.try_push(pushed).is_ok() {
+        pushed += 1;
+    }
+    assert!(pushed > 32);
+    assert_eq!(v.len(), pushed as usize);
+}
+
+#[test]
+fn strings() {
+    use std::fmt::Write;
+
+    let arena = Arena::new();
+    let hello = arena.alloc_str("hello");
+    hello.make_ascii_uppercase();
+    assert_eq!(hello, "HELLO");
+
+    let mut s = ArenaString::new_in(&arena);
+    s.push_str("abc");
+    s.push('\u{e9}');
+    write!(s, "-{}-{:?}", 12, "x").unwrap();
+    assert_eq!(&*s, "abc\u{e9}-12-\"x\"");
+    s.truncate(3);
+    assert_eq!(s.into_str(), "abc");
+
+    let formatted = arena_format!(&arena, "{} + {} = {}", 1, 2, 1 + 2);
+    assert_eq!(formatted, "1 + 2 = 3");
+
+    let sync = SyncArena::new();
+    assert_eq!(arena_format!(&sync, "{:04}", 7), "0007");
+}
+
+#[test]
+fn interner() {
+    let arena = Arena::new();
+    let mut interner = Interner::new_in(&arena);
+
+    let a = interner.intern("a");
+    let b = interner.intern("b");
+    assert_ne!(a, b);
+    assert_eq!(interner.intern("a"), a);
+    assert_eq!(interner.get("b"), Some(b));
+    assert_eq!(interner.get("c"), None);
+    assert_eq!(a.as_str(), "a");
+
+    // Strings and byte slices share storage.
+    let bytes = interner.intern_bytes(b"a");
+    assert_eq!(bytes.as_ptr(), a.as_ptr());
+    let invalid = interner.intern_bytes(&[0xff, 0xfe]);
+    assert_eq!(&*invalid, &[0xff, 0xfe]);
+
+    // Grow the table through several rehashes.
+    let symbols: Vec<_> = (0..1000)
+        .map(|i| interner.intern(arena_format!(&arena, "sym{}", i)))
+        .collect();
+    for (i, &sym) in symbols.iter().enumerate() {
+        assert_eq!(interner.intern(&std::format!("sym{}", i)), sym);
+    }
+    assert_eq!(interner.len(), 1003);
+}
+
+#[test]
+fn sync_interner() {
+    let arena = SyncArena::new();
+    let interner = SyncInterner::new_in(&arena);
+    let symbols: Vec<Vec<_>> = std::thread::scope(|s| {
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                s.spawn(|| {
+                    (0..100)
+                        .map(|i| interner.intern(&std::format!("{}", i % 50)))
+                        .collect()
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    assert_eq!(interner.len(), 50);
+    for worker in &symbols[1..] {
+        assert_eq!(worker, &symbols[0]);
+    }
+}
+
+#[test]
+fn stats() {
+    let record = RefCell::new(Vec::new());
+    let mut arena = Arena::with_source(TraceSource::new(16, &record));
+    assert_eq!(arena.stats(), ArenaStats::default());
+
+    arena.alloc(1u8);
+    arena.alloc(2u32);
+    arena.alloc(3u64);
+    let stats = arena.stats();
+    assert_eq!(stats.slab_count, 1);
+    assert_eq!(stats.total_bytes, HEADER_SIZE + 16);
+    assert_eq!(stats.allocated_bytes, 13);
+    assert_eq!(stats.padding_bytes, 3);
+
+    // The remaining 0 bytes of the first slab are abandoned, and the second
+    // slab has 12 bytes abandoned when the third is pushed.
+    arena.alloc(4u32);
+    arena.alloc_slice(&[5u8; 16]);
+    let stats = arena.stats();
+    assert_eq!(stats.slab_count, 3);
+    assert_eq!(stats.abandoned_bytes, 12);
+    assert_eq!(stats.allocated_bytes, 13 + 4 + 16);
+    assert_eq!(stats.largest_slab, HEADER_SIZE + 16);
+
+    // Rewinding restores the padding count, and keeps the slab which was
+    // pushed as a spare.
+    let checkpoint = arena.checkpoint();
+    arena.alloc(6u8);
+    arena.scope(|sub| {
+        sub.alloc(7u64);
+    });
+    arena.rewind(checkpoint);
+    let rewound = arena.stats();
+    assert_eq!(rewound.allocated_bytes, stats.allocated_bytes);
+    assert_eq!(rewound.padding_bytes, stats.padding_bytes);
+    assert_eq!(rewound.abandoned_bytes, stats.abandoned_bytes);
+    assert_eq!(rewound.slab_count, 4);
+    assert_eq!(rewound.spare_bytes, HEADER_SIZE + 16);
+
+    arena.reset();
+    let stats = arena.stats();
+    assert_eq!(stats.slab_count, 4);
+    assert_eq!(stats.spare_bytes, stats.total_bytes);
+    assert_eq!(stats.allocated_bytes, 0);
+}
+
+#[test]
+fn sync_stats() {
+    let arena = SyncArena::new();
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for i in 0..1000u32 {
+                    arena.alloc(i as u8);
+                    arena.alloc(i);
+                }
+            });
+        }
+        // Stats can be read while other threads are allocating.
+        s.spawn(|| {
+            for _ in 0..1000 {
+                assert!(arena.stats().allocated_bytes <= 4 * 1000 * 5);
+            }
+        });
+    });
+    let stats = arena.stats();
+    assert_eq!(stats.allocated_bytes, 4 * 1000 * 5);
+    // Threads interleave, so each `u32` may be preceded by up to 3 bytes of
+    // padding.
+    assert!(stats.padding_bytes <= 4 * 1000 * 3);
+}
+
+#[test]
+fn geometric_growth() {
+    let record = RefCell::new(Vec::new());
+    let policy = GrowthPolicy::Geometric {
+        initial: 256,
+        max: 1024,
+    };
+    let source = AllocSource::with_policy(policy);
+    let arena = Arena::with_source(TraceSource::with_source(source, &record));
+
+    while record.borrow().len() < 4 {
+        arena.alloc(0u32);
+    }
+    let sizes: Vec<usize> = record.borrow().iter().map(|&(_, size)| size).collect();
+    assert_eq!(sizes, [256, 512, 1024, 1024]);
+
+    let stats = arena.stats();
+    assert_eq!(stats.largest_slab, 1024);
+    assert_eq!(stats.total_bytes, 2816);
+}
+
+#[test]
+fn geometric_growth_large() {
+    let record = RefCell::new(Vec::new());
+    let policy = GrowthPolicy::Geometric {
+        initial: 256,
+        max: 4096,
+    };
+    let source = AllocSource::with_policy(policy);
+    let arena = Arena::with_source(TraceSource::with_source(source, &record));
+
+    // The dedicated slab for the large allocation fits it exactly, and
+    // doesn't count towards the growth of the regular slabs.
+    arena.alloc(0u32);
+    arena.alloc_slice(&[0u8; 1000][..]);
+    while record.borrow().len() < 4 {
+        arena.alloc(0u32);
+    }
+    let sizes: Vec<usize> = record.borrow().iter().map(|&(_, size)| size).collect();
+    assert_eq!(sizes, [256, HEADER_SIZE + 1000, 512, 1024]);
+}
+
+#[test]
+fn custom_growth() {
+    fn policy(index: usize, min_layout: Layout) -> usize {
+        assert!(min_layout.size() >= HEADER_SIZE);
+        HEADER_SIZE + 8 * (index + 1)
+    }
+
+    let record = RefCell::new(Vec::new());
+    let source = AllocSource::with_policy(GrowthPolicy::Custom(policy));
+    let arena = Arena::with_source(TraceSource::with_source(source, &record));
+    for i in 0..6u64 {
+        arena.alloc(i);
+    }
+    let sizes: Vec<usize> = record.borrow().iter().map(|&(_, size)| size - HEADER_SIZE).collect();
+    assert_eq!(sizes, [8, 16, 24]);
+}
+
+#[test]
+fn aligned_slabs() {
+    let source = AllocSource::new(8192).with_slab_align(4096);
+    let arena = Arena::with_source(CheckedSource::new(source));
+
+    // The first allocation in each slab directly follows the header at the
+    // start of a page.
+    let first = arena.alloc(0u32) as *const u32 as usize;
+    assert_eq!(first % 4096, HEADER_SIZE);
+    let mut last = first;
+    while arena.stats().slab_count < 2 {
+        last = arena.alloc(0u32) as *const u32 as usize;
+    }
+    assert_eq!(last % 4096, HEADER_SIZE);
+}
+
+#[test]
+fn buffer_source_layout() {
+    // Offset the buffer so the slab must be aligned within it.
+    let mut buf = [0u8; 257];
+    let offset = 1 + buf[1..].as_ptr().align_offset(8);
+    let source = CheckedSource::new(BufferSource::new(&mut buf[offset..]));
+    let mut arena = Arena::with_source(source);
+    assert!(arena.try_alloc(1u64).is_ok());
+    arena.reset_and_shrink();
+    assert!(arena.try_alloc(2u64).is_ok());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "different layout")]
+fn checked_source_mismatch() {
+    let mut source = CheckedSource::new(AllocSource::new(64));
+    unsafe {
+        let (ptr, layout) = source.alloc_slab(Layout::new::<u64>()).unwrap();
+        let wrong = Layout::from_size_align(layout.size(), 1).unwrap();
+        source.dealloc_slab(ptr, wrong);
+    }
+}
+
+#[test]
+fn region_source() {
+    let mut region = [0u8; 1024];
+    let source = CheckedSource::new(RegionSource::new(&mut region, HEADER_SIZE + 64));
+    let mut arena = Arena::with_source(source);
+
+    // The region is split into many slabs, until it is used up.
+    while arena.try_alloc(0u64).is_ok() {}
+    let slab_count = arena.stats().slab_count;
+    assert!(slab_count > 1);
+
+    // Freed slabs are handed out again.
+    arena.reset_and_shrink();
+    assert_eq!(arena.stats().slab_count, 1);
+    while arena.try_alloc(0u64).is_ok() {}
+    assert_eq!(arena.stats().slab_count, slab_count);
+}
+
+#[test]
+fn region_source_alignment() {
+    #[repr(align(64))]
+    struct Aligned([u8; 1024]);
+
+    let small = Layout::from_size_align(64, 8).unwrap();
+    let large = Layout::from_size_align(64, 64).unwrap();
+
+    let mut region = Aligned([0; 1024]);
+    let mut source = CheckedSource::new(RegionSource::new(&mut region.0, 64));
+    unsafe {
+        // The second slab is at a 64 byte aligned address, but was only
+        // allocated with 8 byte alignment, so isn't reused for `large`.
+        let (a, a_layout) = source.alloc_slab(small).unwrap();
+        let (b, b_layout) = source.alloc_slab(small).unwrap();
+        let (c, c_layout) = source.alloc_slab(small).unwrap();
+        source.dealloc_slab(b, b_layout);
+        let (d, d_layout) = source.alloc_slab(large).unwrap();
+        assert_ne!(d, b);
+        assert_eq!(d_layout.align(), 64);
+        for (ptr, layout) in [(a, a_layout), (c, c_layout), (d, d_layout)] {
+            source.dealloc_slab(ptr, layout);
+        }
+    }
+
+    let mut region = Aligned([0; 1024]);
+    let mut source = RegionSource::new(&mut region.0, 40);
+    unsafe {
+        // Rolling back the last slab also gives back the padding before it.
+        let (a, a_layout) = source.alloc_slab(Layout::new::<u64>()).unwrap();
+        let remaining = source.remaining();
+        let (b, b_layout) = source.alloc_slab(large).unwrap();
+        assert_eq!(source.remaining(), remaining - 24 - 64);
+        source.dealloc_slab(b, b_layout);
+        assert_eq!(source.remaining(), remaining);
+        source.dealloc_slab(a, a_layout);
+        assert_eq!(source.remaining(), 1024);
+    }
+}
+
+#[test]
+fn region_source_uninit() {
+    let mut region = [mem::MaybeUninit::<u8>::uninit(); 512];
+    let arena = Arena::with_source(RegionSource::from_uninit(&mut region, 128));
+    let s = arena.try_alloc_slice(&[1u32, 2, 3]).unwrap();
+    assert_eq!(s, &[1, 2, 3]);
+    assert!(arena.try_alloc_slice(&[0u8; 1024][..]).is_err());
+}
+
+#[test]
+fn stack_source() {
+    let storage = StackSource::<256>::new();
+    let arena = Arena::with_source(&storage);
+    let s = arena.try_alloc_slice(&[1u64, 2, 3]).unwrap();
+    assert_eq!(s, &[1, 2, 3]);
+
+    // The storage can only be used by one arena at a time.
+    let other = Arena::with_source(&storage);
+    assert!(other.try_alloc(0u8).is_err());
+    drop(arena);
+    assert!(other.try_alloc(0u8).is_ok());
+}
+
+#[test]
+fn static_arena() {
+    static STORAGE: StackSource<1024> = StackSource::new();
+    static ARENA: SyncArena<&StackSource<1024>> = SyncArena::with_source(&STORAGE);
+
+    std::thread::scope(|s| {
+        for i in 0..4u32 {
+            s.spawn(move || assert_eq!(ARENA.try_alloc(i), Ok(&mut { i })));
+        }
+    });
+    assert_eq!(ARENA.stats().allocated_bytes, 16);
+}
+
+#[cfg(all(feature = "mmap", target_os = "linux"))]
+#[test]
+fn mmap_source() {
+    use super::source::MmapSource;
+
+    let source = MmapSource::new(1 << 30, 1 << 16).unwrap();
+    let arena = Arena::with_source(CheckedSource::new(source));
+    let first = arena.try_alloc(0u64).unwrap() as *const u64 as usize;
+    assert_eq!(first % 4096, HEADER_SIZE);
+
+    // The slab is grown in place, rather than new slabs being added.
+    for i in 0..100_000u64 {
+        arena.try_alloc(i).unwrap();
+    }
+    assert!(arena.try_alloc_slice(&[7u8; 1 << 20][..]).is_ok());
+    let stats = arena.stats();
+    assert_eq!(stats.slab_count, 1);
+    assert!(stats.total_bytes >= 100_000 * 8 + (1 << 20));
+    assert_eq!(stats.abandoned_bytes, 0);
+}
+
+#[cfg(all(feature = "mmap", target_os = "linux"))]
+#[test]
+fn mmap_source_sync() {
+    use super::source::MmapSource;
+
+    let arena = SyncArena::with_source(MmapSource::new(1 << 30, 1 << 12).unwrap());
+    std::thread::scope(|s| {
+        for t in 0..4u32 {
+            let arena = &arena;
+            s.spawn(move || {
+                for i in 0..10_000u32 {
+                    assert_eq!(*arena.try_alloc(t ^ i).unwrap(), t ^ i);
+                }
+            });
+        }
+    });
+    assert_eq!(arena.stats().allocated_bytes, 4 * 10_000 * 4);
+}
+
+#[cfg(all(feature = "mmap", target_os = "linux"))]
+#[test]
+fn file_source() {
+    use super::source::{FileSource, MappedFile};
+
+    let path = std::env::temp_dir().join(std::format!("data_arena_{}.bin", std::process::id()));
+    let offset = {
+        let source = FileSource::create(&path, 1 << 30, 1 << 12).unwrap();
+        let arena = Arena::with_source(source);
+        arena.try_alloc_slice(&[0u8; 10000][..]).unwrap();
+        let root = arena.try_alloc([1u32, 2, 3, 4]).unwrap();
+        arena.set_root(root);
+        arena.offset_of(root)
+    };
+
+    let file = MappedFile::open(&path).unwrap();
+    assert_eq!(unsafe { file.root::<[u32; 4]>() }, Some(&[1, 2, 3, 4]));
+    assert_eq!(file.root_offset(), Some(offset.offset()));
+    assert_eq!(unsafe { offset.resolve_in(file.as_bytes()) }, &[1, 2, 3, 4]);
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn relative_pointers() {
+    use super::{ArenaOffset, RelPtr};
+
+    struct Node {
+        value: u32,
+        next: RelPtr<Node>,
+    }
+
+    #[repr(align(16))]
+    struct Buf([u8; 512]);
+
+    let mut buf = Buf([0; 512]);
+    let head = {
+        let arena = Arena::with_source(BufferSource::new(&mut buf.0[..]));
+        let mut prev: Option<&mut Node> = None;
+        for value in (0..4).rev() {
+            let node = arena
+                .try_alloc_no_drop(Node {
+                    value,
+                    next: RelPtr::null(),
+                })
+                .unwrap();
+            if let Some(prev) = prev {
+                node.next.set(prev);
+            }
+            prev = Some(node);
+        }
+        let head = prev.unwrap();
+        assert_eq!(unsafe { arena.resolve(arena.offset_of(head)) }.value, 0);
+        arena.offset_of(head).offset()
+    };
+
+    // Move the data to a different address, and follow the list there.
+    let copy = std::boxed::Box::new(Buf(buf.0));
+    let mut node = unsafe { ArenaOffset::<Node>::from_offset(head).resolve_in(&copy.0) };
+    let mut values = Vec::new();
+    loop {
+        values.push(node.value);
+        match unsafe { node.next.get() } {
+            Some(next) => node = next,
+            None => break,
+        }
+    }
+    assert_eq!(values, [0, 1, 2, 3]);
+    let copy_range = copy.0.as_ptr_range();
+    assert!(copy_range.contains(&(node as *const Node as *const u8)));
+}
+
+#[test]
+fn alloc_offset() {
+    let mut buf = [0u8; 256];
+    let arena = Arena::with_source(PanicSource(BufferSource::new(&mut buf[..])));
+    let offsets: Vec<_> = (0..4u32).map(|i| arena.alloc_offset(i)).collect();
+    for (i, &offset) in offsets.iter().enumerate() {
+        assert_eq!(unsafe { *arena.resolve(offset) }, i as u32);
+    }
+}
+
+#[test]
+fn snapshot() {
+    use super::ArenaSnapshot;
+
+    #[repr(align(16))]
+    struct Aligned([u8; 1024]);
+
+    let record = RefCell::new(Vec::new());
+    let mut arena = Arena::with_source(TraceSource::new(64, &record));
+    arena.alloc_slice(&[1u32; 10]);
+    arena.alloc_slice(&[2u32; 10]);
+    arena.alloc(1u8);
+    arena.alloc(3u64);
+    let starts: Vec<usize> = unsafe { arena.snapshot() }
+        .map(|s| s.as_ptr() as usize)
+        .collect();
+    let segments: Vec<Vec<u8>> = unsafe { arena.snapshot() }.map(|s| s.to_vec()).collect();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].len(), 40);
+    assert_eq!(segments[1][..40], [2u32; 10].map(u32::to_ne_bytes).concat()[..]);
+    // The padding before the `u64` is zeroed.
+    let padding = (8 - (starts[1] + 41) % 8) % 8;
+    assert_eq!(segments[1][40], 1);
+    assert!(segments[1][41..41 + padding].iter().all(|&b| b == 0));
+    assert_eq!(segments[1][41 + padding..], 3u64.to_ne_bytes());
+
+    let mut bytes = Vec::new();
+    unsafe { arena.write_snapshot(&mut bytes) }.unwrap();
+    let mut buf = Aligned([0; 1024]);
+    buf.0[..bytes.len()].copy_from_slice(&bytes);
+    let loaded = ArenaSnapshot::from_bytes(&buf.0[..bytes.len()]).unwrap();
+    assert_eq!(loaded.segment_count(), 2);
+    assert!(loaded.segments().eq(segments.iter().map(|s| &s[..])));
+    for (segment, start) in loaded.segments().zip(starts) {
+        assert_eq!(segment.as_ptr() as usize % 16, start % 16);
+    }
+
+    // Corrupt or truncated snapshots are rejected.
+    assert!(ArenaSnapshot::from_bytes(&buf.0[..bytes.len() - 20]).is_none());
+    buf.0[0] = 0;
+    assert!(ArenaSnapshot::from_bytes(&buf.0[..bytes.len()]).is_none());
+}
+
+#[test]
+fn freeze() {
+    use super::{FrozenArena, FrozenRoot};
+
+    struct Numbers;
+    impl<'r> FrozenRoot<'r> for Numbers {
+        type Root = [u32];
+    }
+
+    let arena = Arena::new();
+    arena.alloc_str("unused");
+    let frozen = Arc::new(arena.freeze_with::<Numbers>(|arena| {
+        &*arena.alloc_from_iter(0..100u32, 100)
+    }));
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            let frozen = frozen.clone();
+            s.spawn(move || assert_eq!(frozen.with_root(|root| root.iter().sum::<u32>()), 4950));
+        }
+    });
+
+    let frozen: FrozenArena<_, Numbers> = Arc::try_unwrap(frozen).unwrap();
+    let arena = frozen.thaw();
+    assert_eq!(arena.alloc(5u32), &5);
+    arena.freeze();
+}
+
+#[test]
+fn freeze_graph() {
+    use super::FrozenRoot;
+
+    #[derive(Clone, Copy)]
+    struct Node<'r> {
+        id: usize,
+        edges: &'r [&'r Node<'r>],
+    }
+
+    struct Graph;
+    impl<'r> FrozenRoot<'r> for Graph {
+        type Root = [&'r Node<'r>];
+    }
+
+    // A layered graph, where each node links to every node in the layer
+    // below it.
+    let arena = Arena::new();
+    let frozen = Arc::new(arena.freeze_with::<Graph>(|arena| {
+        let mut layer: &[&Node] = &[];
+        let mut nodes = Vec::new();
+        for depth in 0..4 {
+            let edges = layer;
+            let new_node = |id| &*arena.alloc(Node { id, edges });
+            let next = arena.alloc_from_iter((depth * 3..depth * 3 + 3).map(new_node), 3);
+            nodes.extend_from_slice(next);
+            layer = next;
+        }
+        arena.alloc_slice(&nodes)
+    }));
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            let frozen = frozen.clone();
+            s.spawn(move || {
+                frozen.with_root(|nodes| {
+                    assert_eq!(nodes.len(), 12);
+                    for (i, node) in nodes.iter().enumerate() {
+                        assert_eq!(node.id, i);
+                        let edges: Vec<usize> = node.edges.iter().map(|n| n.id).collect();
+                        let below = (i / 3 * 3).saturating_sub(3)..i / 3 * 3;
+                        assert_eq!(edges, below.collect::<Vec<_>>());
+                    }
+                })
+            });
+        }
+    });
+}
+
+#[test]
+#[should_panic(expected = "registered destructors")]
+fn freeze_with_drops() {
+    let arena = Arena::new();
+    arena.alloc_drop(String::from("hello"));
+    arena.freeze();
+}
+
+#[test]
+fn into_sync_and_back() {
+    let record = RefCell::new(Vec::new());
+    {
+        let arena = Arena::with_source(TraceSource::new(64, &record));
+        let a = arena.alloc(1u32) as *const u32;
+        arena.alloc_slice(&[2u8; 100]);
+
+        let arena = arena.into_sync();
+        assert_eq!(unsafe { *a }, 1);
+        assert_eq!(arena.stats().slab_count, 2);
+        let b = arena.alloc(3u32) as *const u32;
+        assert_eq!(b, unsafe { a.add(1) });
+
+        let arena = arena.into_local();
+        arena.alloc_drop(String::from("dropped"));
+        assert_eq!(unsafe { (*a, *b) }, (1, 3));
+        assert_eq!(record.borrow().len(), 2);
+    }
+    assert!(record.borrow().is_empty());
+}
+
+#[test]
+fn into_local_keeps_drops() {
+    let dropped = Arc::new(());
+    let arena = SyncArena::new();
+    arena.alloc_drop(dropped.clone());
+    let arena = arena.into_local();
+    assert_eq!(Arc::strong_count(&dropped), 2);
+    drop(arena);
+    assert_eq!(Arc::strong_count(&dropped), 1);
+}
+
+#[test]
+fn local_handle() {
+    let arena = SyncArena::with_source(AllocSource::new(1 << 16));
+    let values: Vec<&u64> = std::thread::scope(|s| {
+        let threads: Vec<_> = (0..4u64)
+            .map(|t| {
+                let arena = &arena;
+                s.spawn(move || {
+                    let handle = arena.local_handle_with_chunk_size(1024);
+                    let mut values = Vec::new();
+                    for i in 0..1000 {
+                        values.push(&*handle.alloc(t * 1000 + i));
+                    }
+                    values
+                })
+            })
+            .collect();
+        threads.into_iter().flat_map(|t| t.join().unwrap()).collect()
+    });
+
+    // Allocations outlive the handles which made them.
+    let mut sorted: Vec<u64> = values.iter().map(|&&v| v).collect();
+    sorted.sort_unstable();
+    assert!(sorted.into_iter().eq(0..4000));
+
+    // Handles also work with collections, and grow them in place.
+    let handle = arena.local_handle();
+    let mut vec = ArenaVec::new_in(&handle);
+    vec.extend(0..10u32);
+    let first = vec.as_ptr();
+    vec.extend(10..100u32);
+    assert_eq!(first, vec.as_ptr());
+    assert_eq!(handle.alloc_str("done"), "done");
+    assert_eq!(handle.alloc_slice(&[1u8; 8192][..]).len(), 8192);
+}
+
+#[test]
+fn local_handle_default_chunks() {
+    let arena = SyncArena::new();
+    let handle = arena.local_handle();
+    for i in 0..10_000u64 {
+        assert_eq!(*handle.alloc(i), i);
+    }
+
+    // Chunks are taken from the shared slabs, rather than each being given a
+    // dedicated slab of its own. The default source's slabs are 4KiB.
+    let stats = arena.stats();
+    assert_eq!(stats.largest_slab, 4096);
+    assert!(stats.slab_count > 10_000 * 8 / 4096);
+}
+
+#[test]
+fn spin_lock() {
+    use super::lock::SpinLock;
+
+    // Small slabs, so that threads race to install new ones.
+    let arena = SyncArena::with_source_and_lock(AllocSource::new(256), SpinLock::new());
+    let barrier = std::sync::Barrier::new(8);
+    let values: Vec<&u64> = std::thread::scope(|s| {
+        let threads: Vec<_> = (0..8u64)
+            .map(|t| {
+                let (arena, barrier) = (&arena, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    (0..1000).map(|i| &*arena.alloc(t * 1000 + i)).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        threads.into_iter().flat_map(|t| t.join().unwrap()).collect()
+    });
+
+    let mut sorted: Vec<u64> = values.iter().map(|&&v| v).collect();
+    sorted.sort_unstable();
+    assert!(sorted.into_iter().eq(0..8000));
+    assert!(arena.stats().allocated_bytes >= 8000 * 8);
+
+    static STORAGE: StackSource<1024> = StackSource::new();
+    static ARENA: SyncArena<&StackSource<1024>, SpinLock> =
+        SyncArena::with_source_and_lock(&STORAGE, SpinLock::new());
+    assert_eq!(*ARENA.try_alloc(7u32).unwrap(), 7);
+}
+
+#[test]
+fn epoch_arena() {
+    use super::EpochArena;
+
+    let arena = EpochArena::with_source(AllocSource::new(1 << 12));
+    let guard = arena.pin();
+    let first = check_ptr(guard.alloc(1u64));
+    assert_eq!(guard.epoch(), 0);
+
+    // The first epoch's slabs are retired, but can't be recycled until the
+    // guard pinning it is dropped.
+    assert!(arena.advance_epoch());
+    assert!(!arena.advance_epoch());
+    assert_eq!(*guard.alloc(2u64), 2);
+    drop(guard);
+    assert!(arena.advance_epoch());
+    assert_eq!(arena.epoch(), 2);
+
+    // Now the first slab is reused.
+    let guard = arena.pin();
+    assert_eq!(check_ptr(guard.alloc(3u64)), first);
+    let mut vec = ArenaVec::new_in(&guard);
+    vec.extend(0..100u32);
+    drop(vec);
+    drop(guard);
+
+    // Threads can keep allocating while the epoch advances.
+    std::thread::scope(|s| {
+        for t in 0..4u64 {
+            let arena = &arena;
+            s.spawn(move || {
+                for i in 0..100 {
+                    let guard = arena.pin();
+                    let values = guard.alloc_slice(&[t * 1000 + i; 16]);
+                    assert!(values.iter().all(|&v| v == t * 1000 + i));
+                }
+            });
+        }
+        for _ in 0..100 {
+            arena.advance_epoch();
+        }
+    });
+}
+
+#[test]
+fn arena_errors() {
+    // Sizes which can't be represented are rejected before the source is
+    // asked for anything.
+    let arena = Arena::new();
+    let iter = std::iter::repeat(0u64);
+    assert_eq!(
+        arena.try_alloc_from_iter(iter, usize::MAX).err(),
+        Some(ArenaError::LayoutOverflow)
+    );
+    let mut vec = ArenaVec::<u32, _>::new_in(&arena);
+    assert_eq!(vec.try_reserve(usize::MAX), Err(ArenaError::LayoutOverflow));
+    assert_eq!(arena.stats().slab_count, 0);
+
+    let mut buf = [0u8; 256];
+    let arena = Arena::with_source(BufferSource::new(&mut buf));
+    match arena.try_alloc_slice(&[0u8; 1024][..]) {
+        Err(ArenaError::SourceExhausted { requested }) => assert!(requested.size() >= 1024),
+        other => panic!("unexpected result: {:?}", other),
+    }
 
-    // XXX: Ideally we wouldn't waste the rest of the first allocation if we had
-    // to bump into an oversized allocation.
-    assert_ne!(t2_p + 512, t3_p);
+    let arena = Arena::with_source(AllocSource::new(1024).with_quota(2048));
+    let err = loop {
+        if let Err(err) = arena.try_alloc([0u8; 256]) {
+            break err;
+        }
+    };
+    assert_eq!(err, ArenaError::QuotaExceeded);
+    assert_eq!(arena.stats().total_bytes, 2048);
+    assert_eq!(err.to_string(), "slab source quota exceeded");
 }
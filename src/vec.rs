@@ -0,0 +1,288 @@
+use crate::source::InfallibleSource;
+use crate::{ArenaError, RawArena};
+
+use core::alloc::Layout;
+use core::cmp;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+/// A growable vector whose buffer is allocated in an arena.
+///
+/// When the buffer is the most recent allocation in the arena, it is grown in
+/// place. Once built, the vector can be turned into a slice borrowed from the
+/// arena with [`into_slice`](Self::into_slice).
+pub struct ArenaVec<'arena, T, A: RawArena + ?Sized> {
+    arena: &'arena A,
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+    marker: PhantomData<T>,
+}
+
+unsafe impl<'arena, T: Send, A: RawArena + Sync + ?Sized> Send for ArenaVec<'arena, T, A> {}
+unsafe impl<'arena, T: Sync, A: RawArena + Sync + ?Sized> Sync for ArenaVec<'arena, T, A> {}
+
+/// Compute the layout of a buffer of `cap` elements, for reporting errors
+/// from the infallible methods.
+fn error_layout<T>(cap: Option<usize>) -> Layout {
+    cap.and_then(|cap| Layout::array::<T>(cap).ok())
+        .unwrap_or_else(Layout::new::<T>)
+}
+
+impl<'arena, T, A: RawArena + ?Sized> ArenaVec<'arena, T, A> {
+    /// Create a new empty vector. This does not allocate until elements are
+    /// pushed.
+    pub fn new_in(arena: &'arena A) -> Self {
+        let cap = if mem::size_of::<T>() == 0 {
+            usize::MAX
+        } else {
+            0
+        };
+        ArenaVec {
+            arena,
+            ptr: NonNull::dangling(),
+            len: 0,
+            cap,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn try_with_capacity_in(capacity: usize, arena: &'arena A) -> Result<Self, ArenaError> {
+        let mut vec = Self::new_in(arena);
+        vec.try_reserve(capacity)?;
+        Ok(vec)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Ensure there is space for at least `additional` more elements.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), ArenaError> {
+        let needed = self
+            .len
+            .checked_add(additional)
+            .ok_or(ArenaError::LayoutOverflow)?;
+        if needed <= self.cap {
+            return Ok(());
+        }
+
+        // Try to double the capacity, but settle for the exact amount needed
+        // if that fails.
+        let new_cap = cmp::max(cmp::max(needed, self.cap.saturating_mul(2)), 4);
+        match self.try_grow(new_cap) {
+            Err(_) if new_cap > needed => self.try_grow(needed),
+            result => result,
+        }
+    }
+
+    fn try_grow(&mut self, new_cap: usize) -> Result<(), ArenaError> {
+        let new_layout = Layout::array::<T>(new_cap).map_err(|_| ArenaError::LayoutOverflow)?;
+        let ptr = unsafe {
+            if self.cap == 0 {
+                self.arena.try_alloc_raw(new_layout)?
+            } else {
+                let old_layout =
+                    Layout::array::<T>(self.cap).map_err(|_| ArenaError::LayoutOverflow)?;
+                self.arena
+                    .try_realloc_raw(self.ptr.cast(), old_layout, new_layout)?
+            }
+        };
+        self.ptr = ptr.cast();
+        self.cap = new_cap;
+        Ok(())
+    }
+
+    pub fn try_push(&mut self, value: T) -> Result<(), ArenaError> {
+        self.try_reserve(1)?;
+        unsafe {
+            ptr::write(self.ptr.as_ptr().add(self.len), value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn try_extend_from_slice(&mut self, values: &[T]) -> Result<(), ArenaError>
+    where
+        T: Copy,
+    {
+        self.try_reserve(values.len())?;
+        unsafe {
+            let dst = self.ptr.as_ptr().add(self.len);
+            ptr::copy_nonoverlapping(values.as_ptr(), dst, values.len());
+        }
+        self.len += values.len();
+        Ok(())
+    }
+
+    /// Insert `value` at `index`, shifting all elements after it to the right.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn try_insert(&mut self, index: usize, value: T) -> Result<(), ArenaError> {
+        assert!(index <= self.len, "insertion index out of bounds");
+        self.try_reserve(1)?;
+        unsafe {
+            let p = self.ptr.as_ptr().add(index);
+            ptr::copy(p, p.add(1), self.len - index);
+            ptr::write(p, value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        unsafe { Some(ptr::read(self.ptr.as_ptr().add(self.len))) }
+    }
+
+    /// Shorten the vector to `len` elements, dropping the rest. Has no effect
+    /// if the vector is already shorter.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail =
+            ptr::slice_from_raw_parts_mut(unsafe { self.ptr.as_ptr().add(len) }, self.len - len);
+        self.len = len;
+        unsafe { ptr::drop_in_place(tail) }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Convert the vector into a slice which lives as long as the arena.
+    /// Unused capacity is returned to the arena if possible.
+    ///
+    /// Like `Arena::alloc_no_drop`, the elements will never be dropped.
+    pub fn into_slice(self) -> &'arena mut [T] {
+        let vec = mem::ManuallyDrop::new(self);
+        unsafe {
+            vec.release_capacity(vec.len);
+            slice::from_raw_parts_mut(vec.ptr.as_ptr(), vec.len)
+        }
+    }
+
+    /// Shrink the buffer to `len` elements. This is done in place, so only
+    /// frees memory if the buffer is the most recent allocation.
+    unsafe fn release_capacity(&self, len: usize) {
+        if self.cap == 0 || mem::size_of::<T>() == 0 {
+            return;
+        }
+        let old_layout = Layout::array::<T>(self.cap).unwrap();
+        let new_layout = Layout::array::<T>(len).unwrap();
+        let ptr = self
+            .arena
+            .try_realloc_raw(self.ptr.cast(), old_layout, new_layout);
+        debug_assert_eq!(ptr, Ok(self.ptr.cast()));
+    }
+}
+
+impl<'arena, T, A> ArenaVec<'arena, T, A>
+where
+    A: RawArena + ?Sized,
+    A::Source: InfallibleSource,
+{
+    pub fn with_capacity_in(capacity: usize, arena: &'arena A) -> Self {
+        A::Source::unwrap(Self::try_with_capacity_in(capacity, arena), || {
+            error_layout::<T>(Some(capacity))
+        })
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.len;
+        A::Source::unwrap(self.try_reserve(additional), || {
+            error_layout::<T>(len.checked_add(additional))
+        })
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.reserve(1);
+        let _ = self.try_push(value);
+    }
+
+    pub fn extend_from_slice(&mut self, values: &[T])
+    where
+        T: Copy,
+    {
+        self.reserve(values.len());
+        let _ = self.try_extend_from_slice(values);
+    }
+
+    /// Insert `value` at `index`, shifting all elements after it to the right.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len, "insertion index out of bounds");
+        self.reserve(1);
+        let _ = self.try_insert(index, value);
+    }
+}
+
+impl<'arena, T, A> Extend<T> for ArenaVec<'arena, T, A>
+where
+    A: RawArena + ?Sized,
+    A::Source: InfallibleSource,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'arena, T, A: RawArena + ?Sized> Deref for ArenaVec<'arena, T, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<'arena, T, A: RawArena + ?Sized> DerefMut for ArenaVec<'arena, T, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<'arena, T: fmt::Debug, A: RawArena + ?Sized> fmt::Debug for ArenaVec<'arena, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<'arena, T, A: RawArena + ?Sized> Drop for ArenaVec<'arena, T, A> {
+    fn drop(&mut self) {
+        self.clear();
+        unsafe { self.release_capacity(0) }
+    }
+}
@@ -0,0 +1,221 @@
+//! Model checks of `SyncArena`'s atomics. Run with:
+//!
+//! ```sh
+//! LOOM_MAX_PREEMPTIONS=3 RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! ```
+//!
+//! Without bounding preemptions, the spin lock makes the search too large to
+//! finish.
+//!
+//! Loom only sees its own atomics and cells, so these models check the
+//! protocol around the arena's atomics: slabs' `used` offsets, the current
+//! slab pointer, and the drop list. Reads and writes of slab memory are
+//! invisible to it, except in `alloc_after_shrink`, which shadows them with
+//! loom cells.
+#![cfg(loom)]
+
+use data_arena::lock::SpinLock;
+use data_arena::source::AllocSource;
+use data_arena::SyncArena;
+
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+use std::ptr::NonNull;
+
+type ModelArena = SyncArena<'static, AllocSource, SpinLock>;
+
+fn arena(slab_size: usize) -> Arc<ModelArena> {
+    Arc::new(SyncArena::with_source_and_lock(
+        AllocSource::new(slab_size),
+        SpinLock::new(),
+    ))
+}
+
+/// Allocate `values` from several threads, returning where each one ended up.
+fn alloc_from_threads(arena: &Arc<ModelArena>, values: &[[u64; 2]]) -> Vec<(usize, u64)> {
+    let threads: Vec<_> = values
+        .iter()
+        .map(|&values| {
+            let arena = Arc::clone(arena);
+            thread::spawn(move || {
+                values
+                    .iter()
+                    .map(|&v| (arena.alloc(v) as *mut u64 as usize, v))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    threads
+        .into_iter()
+        .flat_map(|t| t.join().unwrap())
+        .collect()
+}
+
+fn check_allocs(allocs: &[(usize, u64)]) {
+    for (i, &(ptr, value)) in allocs.iter().enumerate() {
+        assert_eq!(unsafe { *(ptr as *const u64) }, value);
+        assert!(allocs[..i].iter().all(|&(other, _)| other != ptr));
+    }
+}
+
+#[test]
+fn concurrent_alloc() {
+    loom::model(|| {
+        // Large enough that every allocation fits in the first slab.
+        let arena = arena(256);
+        arena.alloc(0u64);
+        let allocs = alloc_from_threads(&arena, &[[1, 2], [3, 4]]);
+        check_allocs(&allocs);
+    });
+}
+
+#[test]
+fn slab_replacement() {
+    loom::model(|| {
+        // Each slab only has room for one value, so every allocation races to
+        // install a new slab.
+        let arena = arena(48);
+        let allocs = alloc_from_threads(&arena, &[[1, 2], [3, 4]]);
+        check_allocs(&allocs);
+        assert_eq!(arena.stats().allocated_bytes, 4 * 8);
+    });
+}
+
+#[test]
+fn alloc_after_shrink() {
+    use loom::cell::UnsafeCell;
+    use std::alloc::Layout;
+
+    loom::model(|| {
+        // Large enough that every allocation fits in the first slab.
+        let arena = arena(256);
+
+        // Loom doesn't see writes to slab memory, so each word the threads
+        // write is shadowed by a loom cell, found by its offset from the first
+        // allocation in the slab.
+        let base = arena.alloc(0u64) as *mut u64 as usize;
+        let words: Arc<Vec<UnsafeCell<u64>>> =
+            Arc::new((0..8).map(|_| UnsafeCell::new(0)).collect());
+        let write = move |words: &[UnsafeCell<u64>], ptr: NonNull<u8>, value: u64| {
+            words[(ptr.as_ptr() as usize - base) / 8].with_mut(|word| unsafe { *word = value });
+            unsafe { ptr.cast::<u64>().as_ptr().write(value) };
+        };
+
+        let layout = Layout::new::<u64>();
+        let thread = {
+            let (arena, words) = (Arc::clone(&arena), Arc::clone(&words));
+            thread::spawn(move || unsafe {
+                // Shrinking the block to nothing gives it back, so the other
+                // thread may be handed the same bytes, and must see this
+                // write happen before its own.
+                let ptr = arena.try_alloc_raw(layout).unwrap();
+                write(&words, ptr, 1);
+                arena
+                    .try_realloc_raw(ptr, layout, Layout::new::<()>())
+                    .unwrap();
+            })
+        };
+        unsafe {
+            let ptr = arena.try_alloc_raw(layout).unwrap();
+            write(&words, ptr, 2);
+            assert_eq!(*ptr.cast::<u64>().as_ptr(), 2);
+        }
+        thread.join().unwrap();
+    });
+}
+
+#[test]
+fn realloc_during_replacement() {
+    use std::alloc::Layout;
+
+    loom::model(|| {
+        // Each slab only has room for one value, so the allocation on the
+        // other thread may install a new slab while this one resizes in it.
+        let arena = arena(48);
+        let thread = {
+            let arena = Arc::clone(&arena);
+            thread::spawn(move || arena.alloc(2u64) as *mut u64 as usize)
+        };
+
+        let (small, large) = (Layout::new::<u64>(), Layout::new::<[u64; 2]>());
+        unsafe {
+            let ptr = arena.try_alloc_raw(small).unwrap().cast::<u64>();
+            ptr.as_ptr().write(1);
+            let ptr = arena.try_realloc_raw(ptr.cast(), small, large).unwrap();
+            let ptr = ptr.cast::<[u64; 2]>().as_ptr();
+            (*ptr)[1] = 1;
+            assert_eq!(*ptr, [1, 1]);
+            // Shrinking returns the space to the slab if it is still the
+            // most recent allocation.
+            arena
+                .try_realloc_raw(NonNull::new_unchecked(ptr).cast(), large, small)
+                .unwrap();
+        }
+
+        let other = thread.join().unwrap();
+        assert_eq!(unsafe { *(other as *const u64) }, 2);
+    });
+}
+
+#[test]
+fn drop_after_threads() {
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    loom::model(|| {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let arena = arena(48);
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let (arena, dropped) = (Arc::clone(&arena), Arc::clone(&dropped));
+                thread::spawn(move || {
+                    arena.alloc_drop(Counted(dropped));
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        // Dropping the last reference on this thread runs destructors
+        // registered by the others, and frees their slabs.
+        drop(arena);
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+    });
+}
+
+#[test]
+fn epoch_reclaim() {
+    use data_arena::EpochArena;
+
+    loom::model(|| {
+        // Each slab only has room for one value, so a recycled slab is reused
+        // by the next allocation.
+        let arena = Arc::new(EpochArena::with_source_and_lock(
+            AllocSource::new(48),
+            SpinLock::new(),
+        ));
+        let thread = {
+            let arena = Arc::clone(&arena);
+            thread::spawn(move || {
+                let guard = arena.pin();
+                let value = guard.alloc(1u64);
+                thread::yield_now();
+                assert_eq!(*value, 1);
+            })
+        };
+
+        // If the thread's slab were recycled while it was pinned, this would
+        // overwrite its value.
+        arena.advance_epoch();
+        arena.advance_epoch();
+        *arena.pin().alloc(2u64) = 2;
+        thread.join().unwrap();
+    });
+}